@@ -1,9 +1,10 @@
 extern crate cpp_build;
-extern crate gcc;
+
+use std::env;
+
 fn main() {
-    cpp_build::build("src/main.rs");
-    gcc::Build::new()
-        .file("imgui/imgui.cpp")
-        .file("imgui/imgui_draw.cpp")
-        .compile("libimgui.lib");
-}
\ No newline at end of file
+    // imgui-sys compiles imgui and reports where its headers live.
+    let imgui = env::var("DEP_IMGUI_THIRD_PARTY").expect("imgui-sys did not report its sources");
+    println!("cargo:rerun-if-changed=src");
+    cpp_build::Config::new().include(imgui).build("src/main.rs");
+}
@@ -0,0 +1,131 @@
+use cli::{annotation_from_args, flag_value, formula_from_args, palettes_from_args, parse_arg, parse_pair,
+          positive_flag};
+use export::{FRAME_ZOOM, emit_manifest, export_frames, export_pyramid, export_view_raw, export_view_svg, raw_to_png,
+             render_tile, save_png};
+use state::load_view_state;
+use mandelbrot::{ColoringMode, Config, FractalKind, KernelWidth, RendererBuilder, INITIAL_VIEW};
+use mandelbrot::fit_bounds_to_aspect;
+use num::Complex;
+use std::error::Error;
+use std::f32;
+use std::f64;
+use std::process;
+
+const HEADLESS_PATH: &'static str = "mandelbrot.png";
+
+// One frame of the initial view, or of the --load state, straight to a PNG;
+// no window, imgui or event loop is touched.
+fn render_headless(config: &Config, args: &[String], path: &str) -> Result<(), Box<dyn Error>> {
+    let palettes     = palettes_from_args(args);
+    let mut view     = INITIAL_VIEW;
+    let mut rotation = 0.0;
+    let mut limit    = config.limit;
+    let mut fractal  = FractalKind::Mandelbrot;
+    let mut palette  = 0;
+    let mut coloring = ColoringMode::Palette;
+    let mut center   = None;
+    if let Some(state) = flag_value(args, "--load") {
+        load_view_state(state)?.apply(&mut view, &mut center, &mut rotation, &mut limit, &mut fractal, &mut palette,
+                                      &mut coloring, palettes.len())?;
+    }
+    let view   = fit_bounds_to_aspect(view.upper_left, view.lower_right, config.width, config.height);
+    let pixels = RendererBuilder::new()
+        .config(config)
+        .limit(limit)
+        .supersample(config.supersample)
+        .rotation(rotation)
+        .fractal(fractal)
+        .coloring(coloring)
+        .palette(&palettes[palette].1)
+        .formula(formula_from_args(args))
+        .center(center)
+        .build()
+        .render(view);
+    save_png(&pixels, config.width, config.height, path)?;
+    println!("Saved {}.", path);
+    Ok(())
+}
+
+fn frame_zoom(args: &[String]) -> f64 {
+    flag_value(args, "--zoom").and_then(|z| z.parse().ok()).filter(|&z: &f64| z > 0.0).unwrap_or(FRAME_ZOOM)
+}
+
+// Flags that run one batch job instead of opening the viewer. They may come
+// anywhere on the command line, with the job's positional arguments right
+// after the flag.
+const BATCH_MODES: &'static [&'static str] = &["--emit-manifest", "--render-tile", "--export-svg", "--export-raw",
+                                              "--raw-to-png", "--export-pyramid", "--export-frames", "--record",
+                                              "--headless"];
+
+pub fn run_batch_mode(args: &[String], config: &Config) -> bool {
+    let at = match args.iter().skip(1).position(|a| BATCH_MODES.contains(&a.as_str())) {
+        Some(index) => index + 1,
+        None        => return false,
+    };
+    let result = match args[at].as_str() {
+        "--emit-manifest" => {
+            let path: String = parse_arg(args, at + 1, "manifest path");
+            emit_manifest(config, &path, parse_arg(args, at + 2, "column count"),
+                          parse_arg(args, at + 3, "row count")).map_err(|e| e.into())
+        },
+        "--render-tile" => {
+            let path: String = parse_arg(args, at + 1, "manifest path");
+            render_tile(config, &path, parse_arg(args, at + 2, "tile index"))
+        },
+        "--export-svg" => {
+            let path: String   = parse_arg(args, at + 1, "svg path");
+            let levels: String = parse_arg(args, at + 2, "contour levels");
+            let levels: Vec<f32> = levels.split(',').map(|l| l.trim().parse().ok()).collect::<Option<_>>()
+                .unwrap_or_else(|| {
+                    println!("Contour levels must be comma-separated numbers.");
+                    process::exit(1);
+                });
+            let view = fit_bounds_to_aspect(INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right,
+                                            config.width, config.height);
+            export_view_svg(config, view, KernelWidth::fastest(), &levels, &path).map_err(|e| e.into())
+        },
+        "--export-raw" => {
+            let path: String = parse_arg(args, at + 1, "raw path");
+            let kind: String = parse_arg(args, at + 2, "buffer kind (framebuffer or iterations)");
+            export_view_raw(config, &path, kind == "iterations").map_err(|e| e.into())
+        },
+        "--raw-to-png" => {
+            let raw_path: String = parse_arg(args, at + 1, "raw path");
+            let png_path: String = parse_arg(args, at + 2, "png path");
+            raw_to_png(&raw_path, &png_path)
+        },
+        "--export-pyramid" => {
+            let dir: String = parse_arg(args, at + 1, "output directory");
+            let center      = Complex { re: parse_arg(args, at + 2, "center re"),
+                                        im: parse_arg(args, at + 3, "center im") };
+            export_pyramid(config, &dir, center, parse_arg(args, at + 4, "max level"),
+                           parse_arg(args, at + 5, "tile size"))
+        },
+        "--export-frames" => {
+            let target = Complex { re: parse_arg(args, at + 2, "target re"),
+                                   im: parse_arg(args, at + 3, "target im") };
+            export_frames(config, parse_arg(args, at + 1, "frame count"), target, frame_zoom(args),
+                          annotation_from_args(args).as_ref())
+        },
+        "--record" => {
+            let (re, im) = flag_value(args, "--center").and_then(parse_pair).unwrap_or_else(|| {
+                println!("--record needs --center <re>,<im>.");
+                process::exit(1);
+            });
+            let frames = positive_flag(args, "--frames").unwrap_or_else(|| {
+                println!("--record needs --frames <count>.");
+                process::exit(1);
+            });
+            export_frames(config, frames, Complex { re, im }, frame_zoom(args), annotation_from_args(args).as_ref())
+        },
+        "--headless" => {
+            render_headless(config, args, flag_value(args, "--out").unwrap_or(HEADLESS_PATH))
+        },
+        _ => unreachable!(),
+    };
+    if let Err(e) = result {
+        println!("Error: {}", e);
+        process::exit(1);
+    }
+    true
+}
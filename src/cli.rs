@@ -0,0 +1,140 @@
+use export::Annotation;
+use mandelbrot::{Config, DownsampleFilter, Formula, MAX_REFERENCE_BITS, PALETTES};
+use num::Complex;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::f32;
+use std::process;
+
+pub fn parse_pair<T: std::str::FromStr>(value: &str) -> Option<(T, T)> {
+    let mut parts = value.splitn(2, ',');
+    match (parts.next().and_then(|p| p.trim().parse().ok()), parts.next().and_then(|p| p.trim().parse().ok())) {
+        (Some(a), Some(b)) => Some((a, b)),
+        _                  => None,
+    }
+}
+
+pub fn annotation_from_args(args: &[String]) -> Option<Annotation> {
+    if !args.iter().any(|a| a == "--annotate") {
+        return None;
+    }
+    let point    = flag_value(args, "--annotate-point").and_then(parse_pair)
+        .map(|(re, im)| Complex { re, im });
+    let position = flag_value(args, "--annotate-pos").and_then(parse_pair).unwrap_or((10, 10));
+    let color    = flag_value(args, "--annotate-color")
+        .and_then(|c| u32::from_str_radix(c.trim_start_matches('#'), 16).ok())
+        .unwrap_or(0x00FF_FFFF);
+    Some(Annotation { point, position, color })
+}
+
+// Value of `flag` given as `--flag value` or `--flag=value`.
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).map(|v| v.as_str())
+        .or_else(|| args.iter().filter_map(|a| {
+            if a.starts_with(flag) && a[flag.len() ..].starts_with('=') { Some(&a[flag.len() + 1 ..]) } else { None }
+        }).next())
+}
+
+pub fn positive_flag(args: &[String], flag: &str) -> Option<usize> {
+    flag_value(args, flag).and_then(|v| v.parse().ok()).filter(|&v| v > 0)
+}
+
+// --width, --height, --limit, --threads, --supersample, --gamma,
+// --reference-bits and --export-filter (box, triangle or lanczos) override the
+// defaults; zero or unparsable values are ignored, and so are reference bits
+// past MAX_REFERENCE_BITS.
+pub fn config_from_args(args: &[String]) -> Config {
+    let default = Config::default();
+    Config {
+        width:          positive_flag(args, "--width").unwrap_or(default.width),
+        height:         positive_flag(args, "--height").unwrap_or(default.height),
+        limit:          positive_flag(args, "--limit").unwrap_or(default.limit as usize) as u32,
+        num_threads:    positive_flag(args, "--threads").unwrap_or(default.num_threads),
+        supersample:    positive_flag(args, "--supersample").unwrap_or(default.supersample),
+        gamma:          flag_value(args, "--gamma").and_then(|g| g.parse().ok()).filter(|&g: &f32| g > 0.0)
+                            .unwrap_or(default.gamma),
+        reference_bits: positive_flag(args, "--reference-bits").filter(|&bits| bits <= MAX_REFERENCE_BITS as usize)
+                            .unwrap_or(default.reference_bits as usize) as u32,
+        export_filter:  flag_value(args, "--export-filter").and_then(filter_from_name)
+                            .unwrap_or(default.export_filter),
+    }
+}
+
+fn filter_from_name(name: &str) -> Option<DownsampleFilter> {
+    match name {
+        "box"      => Some(DownsampleFilter::Box),
+        "triangle" => Some(DownsampleFilter::Triangle),
+        "lanczos"  => Some(DownsampleFilter::Lanczos),
+        _          => None,
+    }
+}
+
+pub fn parse_arg<T: std::str::FromStr>(args: &[String], index: usize, name: &str) -> T {
+    args.get(index).and_then(|a| a.parse().ok()).unwrap_or_else(|| {
+        println!("Missing or invalid {}.", name);
+        process::exit(1);
+    })
+}
+
+// `--ui-scale <factor>` or MANDELBROT_UI_SCALE.
+pub fn ui_scale(args: &[String]) -> f32 {
+    flag_value(args, "--ui-scale").map(|v| v.to_string())
+        .or_else(|| env::var("MANDELBROT_UI_SCALE").ok())
+        .and_then(|v| v.parse::<f32>().ok())
+        .map(|scale| scale.max(1.0).min(4.0))
+        .unwrap_or(1.0)
+}
+
+pub fn formula_from_args(args: &[String]) -> Option<Formula> {
+    flag_value(args, "--formula").and_then(|text| match Formula::parse(text) {
+        Ok(formula) => Some(formula),
+        Err(e)      => {
+            println!("Invalid formula '{}': {}. Falling back to z*z + c.", text, e);
+            None
+        },
+    })
+}
+
+// A palette name, NUL-terminated for imgui, and its stops.
+pub type NamedPalette = (String, Vec<(f32, f32, f32)>);
+
+// Reads one "r g b" stop per line, channels 0-255. Blank lines and lines
+// starting with # are skipped.
+fn load_palette(path: &str) -> Result<Vec<(f32, f32, f32)>, String> {
+    let file      = File::open(path).map_err(|e| e.to_string())?;
+    let mut stops = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let channels: Result<Vec<f32>, _> = line.split_whitespace().map(|v| v.parse::<f32>()).collect();
+        match channels {
+            Ok(ref c) if c.len() == 3 && c.iter().all(|&c| (0.0..=255.0).contains(&c)) => {
+                stops.push((c[0], c[1], c[2]))
+            },
+            _ => return Err(format!("line {} is not \"r g b\" with channels 0-255", number + 1)),
+        }
+    }
+    if stops.len() < 2 {
+        return Err("a palette needs at least two stops".to_string());
+    }
+    Ok(stops)
+}
+
+// The built-in palettes, preceded by the one from `--palette <file>` if it
+// loads.
+pub fn palettes_from_args(args: &[String]) -> Vec<NamedPalette> {
+    let mut palettes: Vec<NamedPalette> = PALETTES.iter()
+        .map(|&(name, stops)| (name.to_string(), stops.to_vec()))
+        .collect();
+    if let Some(path) = flag_value(args, "--palette") {
+        match load_palette(path) {
+            Ok(stops) => palettes.insert(0, (format!("{}\0", path), stops)),
+            Err(e)    => println!("Invalid palette '{}': {}. Falling back to the built-in palettes.", path, e),
+        }
+    }
+    palettes
+}
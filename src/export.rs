@@ -0,0 +1,612 @@
+use overlay::draw_text;
+use mandelbrot::{CancelToken, Config, KernelWidth, PaletteLut, RenderSettings, Renderer, RendererBuilder, View,
+                 COLORS, DEFAULT_GAMMA, INITIAL_VIEW};
+use mandelbrot::{color, fit_bounds_to_aspect, pixel_to_point, point_to_pixel, view_center};
+use num::Complex;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::f32;
+use std::f64;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Four f32 channels, from Renderer::colorize_linear:
+// - R, G and B: the palette color in linear light, computed in f32 rather
+//   than decoded from the 8-bit frame, so 1.0 is full palette intensity and
+//   compositing tools need no further gamma step.
+// - iterations: the smooth iteration count, the limit inside the set and NaN
+//   for error pixels, to recolor or threshold the render later.
+fn export_exr(samples: &[[f32; 4]], width: usize, height: usize, path: &str) -> exr::error::UnitResult {
+    use exr::prelude::*;
+    let channel  = |name: &str, index: usize| {
+        AnyChannel::new(name, FlatSamples::F32(samples.iter().map(|sample| sample[index]).collect()))
+    };
+    let channels = AnyChannels::sort(SmallVec::from_vec(vec![channel("R", 0), channel("G", 1), channel("B", 2),
+                                                             channel("iterations", 3)]));
+    Image::from_channels((width, height), channels).write().to_file(path)
+}
+
+const EXPORT_SUPERSAMPLE: usize = 2;
+
+pub fn export_view_exr(config: &Config, view: View, kernel: KernelWidth) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path      = format!("mandelbrot_{}.exr", timestamp);
+    let mut renderer = RendererBuilder::new()
+        .config(config)
+        .kernel(kernel)
+        .supersample(EXPORT_SUPERSAMPLE)
+        .downsample_filter(config.export_filter)
+        .build();
+    renderer.iterate(view, (0.0, 0.0), &CancelToken::new());
+    match export_exr(&renderer.colorize_linear(), config.width, config.height, &path) {
+        Ok(()) => println!("Exported {}.", path),
+        Err(e) => println!("Failed to export {}: {}", path, e),
+    }
+}
+
+pub const CONTOUR_LEVELS:    &'static [f32] = &[5.0, 10.0, 20.0, 40.0, 80.0];
+// Max distance in pixels a simplified polyline may stray from the traced one.
+const CONTOUR_TOLERANCE: f32            = 0.5;
+
+// Ramer-Douglas-Peucker: keeps only the points that bend the line by more
+// than `tolerance`, which collapses the long collinear runs marching squares
+// produces along smooth bands.
+fn simplify(line: &[(f32, f32)], tolerance: f32) -> Vec<(f32, f32)> {
+    if line.len() < 3 {
+        return line.to_vec();
+    }
+    let (first, last) = (line[0], line[line.len() - 1]);
+    let (dx, dy)      = (last.0 - first.0, last.1 - first.1);
+    let length        = (dx * dx + dy * dy).sqrt();
+    let mut farthest  = (0, 0.0);
+    for (i, p) in line.iter().enumerate().take(line.len() - 1).skip(1) {
+        let distance = if length > 0.0 {
+            (dy * (p.0 - first.0) - dx * (p.1 - first.1)).abs() / length
+        } else {
+            ((p.0 - first.0).powi(2) + (p.1 - first.1).powi(2)).sqrt()
+        };
+        if distance > farthest.1 {
+            farthest = (i, distance);
+        }
+    }
+    if farthest.1 <= tolerance {
+        return vec![first, last];
+    }
+    let mut simplified = simplify(&line[.. farthest.0 + 1], tolerance);
+    simplified.pop();
+    simplified.extend(simplify(&line[farthest.0 ..], tolerance));
+    simplified
+}
+
+// Marching squares over `values`, chained into polylines. Grid edges get ids
+// shared by both cells touching them: horizontal edge (x, y) is
+// 2 * (y * width + x), the vertical one below the same corner is that plus 1.
+fn contour_lines(values: &[f32], width: usize, height: usize, level: f32) -> Vec<Vec<(f32, f32)>> {
+    let mut points: HashMap<usize, (f32, f32)> = HashMap::new();
+    let mut links:  HashMap<usize, Vec<usize>> = HashMap::new();
+    for y in 0 .. height.saturating_sub(1) {
+        for x in 0 .. width.saturating_sub(1) {
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            let edges   = [2 * (y * width + x),
+                           2 * (y * width + x + 1) + 1,
+                           2 * ((y + 1) * width + x),
+                           2 * (y * width + x) + 1];
+            let mut crossings = Vec::with_capacity(4);
+            for i in 0 .. 4 {
+                let (a, b) = (corners[i], corners[(i + 1) % 4]);
+                let (va, vb) = (values[a.1 * width + a.0], values[b.1 * width + b.0]);
+                if (va < level) != (vb < level) {
+                    let t = (level - va) / (vb - va);
+                    points.insert(edges[i], (a.0 as f32 + t * (b.0 as f32 - a.0 as f32),
+                                             a.1 as f32 + t * (b.1 as f32 - a.1 as f32)));
+                    crossings.push(edges[i]);
+                }
+            }
+            // Saddles cross all four edges; pairing them in order keeps the
+            // two segments from intersecting.
+            for pair in crossings.chunks(2) {
+                links.entry(pair[0]).or_default().push(pair[1]);
+                links.entry(pair[1]).or_default().push(pair[0]);
+            }
+        }
+    }
+
+    // Open lines start at the image border, where an edge has one link.
+    let mut starts: Vec<(bool, usize)> = links.iter().map(|(&e, l)| (l.len() != 1, e)).collect();
+    starts.sort();
+    let mut visited = HashSet::new();
+    let mut lines   = Vec::new();
+    for (_, start) in starts {
+        if !visited.insert(start) {
+            continue;
+        }
+        let mut line    = vec![points[&start]];
+        let mut current = start;
+        while let Some(&next) = links[&current].iter().find(|e| !visited.contains(*e)) {
+            visited.insert(next);
+            line.push(points[&next]);
+            current = next;
+        }
+        if line.len() > 2 && links[&current].contains(&start) {
+            line.push(points[&start]);
+        }
+        lines.push(simplify(&line, CONTOUR_TOLERANCE));
+    }
+    lines
+}
+
+fn export_svg(values: &[f32], width: usize, height: usize, levels: &[f32], path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+             width, height, width, height)?;
+    for &level in levels {
+        let stroke = color(level, COLORS);
+        writeln!(out, "<g fill=\"none\" stroke=\"#{:06x}\" stroke-width=\"1\">", stroke)?;
+        for line in contour_lines(values, width, height, level) {
+            write!(out, "<polyline points=\"")?;
+            for &(x, y) in &line {
+                write!(out, "{:.2},{:.2} ", x, y)?;
+            }
+            writeln!(out, "\"/>")?;
+        }
+        writeln!(out, "</g>")?;
+    }
+    writeln!(out, "</svg>")
+}
+
+pub fn export_view_svg(config: &Config, view: View, kernel: KernelWidth, levels: &[f32], path: &str) -> io::Result<()> {
+    let mut renderer = RendererBuilder::new().config(config).kernel(kernel).build();
+    renderer.iterate(view, (0.0, 0.0), &CancelToken::new());
+    export_svg(&renderer.cache().values(), config.width, config.height, levels, path)?;
+    println!("Exported {}.", path);
+    Ok(())
+}
+
+pub fn save_png(pixels: &[u32], width: usize, height: usize, path: &str) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut rgb = Vec::with_capacity(pixels.len() * 3);
+    for pixel in pixels {
+        rgb.push((pixel >> 16) as u8);
+        rgb.push((pixel >> 8)  as u8);
+        rgb.push(*pixel        as u8);
+    }
+    encoder.write_header()?.write_image_data(&rgb)
+}
+
+// Names a screenshot after the view corners and iteration limit, so the shot
+// can be rendered again later.
+pub fn screenshot_path(view: View, limit: u32, extension: &str) -> String {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    format!("mandelbrot_{}_{}{:+}i_{}{:+}i_limit{}.{}", timestamp,
+            view.upper_left.re, view.upper_left.im, view.lower_right.re, view.lower_right.im, limit, extension)
+}
+
+// Binary P6, which needs no codec at all.
+pub fn write_ppm(buffer: &[u32], width: usize, height: usize, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    for pixel in buffer {
+        out.write_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8])?;
+    }
+    out.flush()
+}
+
+// A failed write only gets reported; it must not take the window down.
+pub fn save_screenshot(buffer: &[u32], width: usize, height: usize, path: &str) {
+    match save_png(buffer, width, height, path) {
+        Ok(()) => println!("Saved {}.", path),
+        Err(e) => println!("Failed to save {}: {}", path, e),
+    }
+}
+
+// Burned into exported frames when --annotate is given.
+pub struct Annotation {
+    // None annotates the center of each frame.
+    pub point:    Option<Complex<f64>>,
+    pub position: (usize, usize),
+    pub color:    u32,
+}
+
+fn annotate(pixels: &mut [u32], renderer: &Renderer, view: View, zoom: f64, annotation: &Annotation) {
+    let text = annotation_text(renderer, view, zoom, annotation);
+    draw_text(pixels, renderer.width, annotation.position, &text, annotation.color);
+}
+
+// The count is the one `renderer` cached for the frame, at the limit it was
+// rendered with; a point outside the frame has none and prints as "n=-".
+fn annotation_text(renderer: &Renderer, view: View, zoom: f64, annotation: &Annotation) -> String {
+    let point    = annotation.point.unwrap_or(view_center(view));
+    let bounds   = (renderer.width * renderer.supersample, renderer.height * renderer.supersample);
+    let settings = renderer.settings();
+    let (x, y)   = point_to_pixel(bounds, point, view.upper_left, view.lower_right, settings.pixel_aspect,
+                                  settings.rotation);
+    let inside   = x >= 0.0 && y >= 0.0 && (x as usize) < bounds.0 && (y as usize) < bounds.1;
+    let count    = if inside { renderer.cache().count(y as usize * bounds.0 + x as usize) } else { None };
+    let count    = count.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string());
+    format!("n={} {:.6}{:+.6}i x{:.1}", count, point.re, point.im, zoom)
+}
+
+// Default factor each exported frame shrinks the view around its target by,
+// see --zoom.
+pub const FRAME_ZOOM: f64 = 1.05;
+
+// Renders `count` frames zooming from the initial view towards `target` by
+// `step` per frame, as frame_0000.png onwards for ffmpeg's %04d pattern; the
+// counter widens past 9999 frames.
+pub fn export_frames(config:     &Config,
+                     count:      usize,
+                     target:     Complex<f64>,
+                     step:       f64,
+                     annotation: Option<&Annotation>) -> Result<(), Box<dyn Error>> {
+    let initial      = fit_bounds_to_aspect(INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right,
+                                            config.width, config.height);
+    let half_re      = (initial.lower_right.re - initial.upper_left.re) / 2.0;
+    let half_im      = (initial.upper_left.im - initial.lower_right.im) / 2.0;
+    let mut renderer = RendererBuilder::new().config(config).build();
+    let digits       = cmp::max(4, count.saturating_sub(1).to_string().len());
+    for frame in 0 .. count {
+        let zoom = step.powi(frame as i32);
+        let view = View {
+            upper_left:  Complex {re: target.re - half_re / zoom, im: target.im + half_im / zoom},
+            lower_right: Complex {re: target.re + half_re / zoom, im: target.im - half_im / zoom},
+        };
+        let mut pixels = renderer.render(view);
+        if let Some(annotation) = annotation {
+            annotate(&mut pixels, &renderer, view, zoom, annotation);
+        }
+        let path = format!("frame_{:01$}.png", frame, digits);
+        save_png(&pixels, config.width, config.height, &path)?;
+        println!("Rendered {}.", path);
+    }
+    Ok(())
+}
+
+// One window of the manifest's frame: its corners are the whole view's, and
+// `origin` places the tile in the columns x rows frame of `frame` pixels.
+#[derive(Serialize, Deserialize)]
+struct TileJob {
+    upper_left:  (f64, f64),
+    lower_right: (f64, f64),
+    frame:       (usize, usize),
+    origin:      (usize, usize),
+    width:       usize,
+    height:      usize,
+    // Iteration limit of the whole manifest view, see frame_limit.
+    limit:       u32,
+    output:      String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TileManifest {
+    columns: usize,
+    rows:    usize,
+    tiles:   Vec<TileJob>,
+}
+
+// Iteration limit a full-frame render of `view` runs with. Tiles of it render
+// at exactly this limit, without a zoom bonus for their own smaller extent, so
+// they match their part of the full frame.
+fn frame_limit(config: &Config, view: View) -> u32 {
+    RenderSettings { limit: config.limit, ..RenderSettings::default() }.limit_at(view.upper_left, view.lower_right)
+}
+
+fn tile_builder(config: &Config, width: usize, height: usize, limit: u32) -> RendererBuilder {
+    RendererBuilder::new().config(config).size(width, height).limit(limit).limit_per_octave(0)
+}
+
+// Splits `view` into a columns x rows grid of config-sized tiles. Each tile
+// renders as a window of the whole frame, sampling exactly the points a
+// single render of the frame would, so the tiles stitch back into that image
+// pixel for pixel.
+fn tile_manifest(config: &Config, view: View, columns: usize, rows: usize) -> TileManifest {
+    let (width, height) = (config.width, config.height);
+    let limit           = frame_limit(config, view);
+    let mut tiles = Vec::with_capacity(columns * rows);
+    for row in 0 .. rows {
+        for column in 0 .. columns {
+            tiles.push(TileJob {
+                upper_left:  (view.upper_left.re,  view.upper_left.im),
+                lower_right: (view.lower_right.re, view.lower_right.im),
+                frame:       (columns * width, rows * height),
+                origin:      (column * width, row * height),
+                width,
+                height,
+                limit,
+                output:      format!("tile_{:04}_{:04}.png", row, column),
+            });
+        }
+    }
+    TileManifest { columns, rows, tiles }
+}
+
+pub fn emit_manifest(config: &Config, path: &str, columns: usize, rows: usize) -> io::Result<()> {
+    let view     = fit_bounds_to_aspect(INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right,
+                                        columns * config.width, rows * config.height);
+    let manifest = tile_manifest(config, view, columns, rows);
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &manifest)?;
+    println!("Wrote {} tiles to {}.", manifest.tiles.len(), path);
+    Ok(())
+}
+
+fn tile_pixels(config: &Config, tile: &TileJob) -> Vec<u32> {
+    let view = View {
+        upper_left:  Complex {re: tile.upper_left.0,  im: tile.upper_left.1},
+        lower_right: Complex {re: tile.lower_right.0, im: tile.lower_right.1},
+    };
+    tile_builder(config, tile.width, tile.height, tile.limit).window(tile.frame, tile.origin).build().render(view)
+}
+
+pub fn render_tile(config: &Config, path: &str, index: usize) -> Result<(), Box<dyn Error>> {
+    let manifest: TileManifest = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let tile = manifest.tiles.get(index)
+        .ok_or_else(|| format!("tile {} out of range, manifest has {}", index, manifest.tiles.len()))?;
+    save_png(&tile_pixels(config, tile), tile.width, tile.height, &tile.output)?;
+    println!("Rendered tile {} to {}.", index, tile.output);
+    Ok(())
+}
+
+const RAW_MAGIC:   &'static [u8; 4] = b"MBRW";
+const RAW_VERSION: u16              = 1;
+// Magic, version, dtype, width, height and the four view coordinates.
+const RAW_HEADER:  u64              = 4 + 2 + 1 + 4 + 4 + 4 * 8;
+
+// Contents of a raw export; the variant is the header's dtype byte.
+enum RawBuffer {
+    // 0x00RRGGBB pixels.
+    Framebuffer(Vec<u32>),
+    // Smooth iteration counts from the iteration cache.
+    Iterations(Vec<f32>),
+}
+
+// Raw exports start with a fixed little-endian header:
+//   magic "MBRW", version u16, dtype u8 (0 framebuffer, 1 iterations),
+//   width u32, height u32, then the view as upper_left.re, upper_left.im,
+//   lower_right.re, lower_right.im f64s,
+// followed by width * height 4-byte samples.
+fn export_raw(path: &str, buffer: &RawBuffer, width: usize, height: usize, view: View) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let dtype   = match *buffer { RawBuffer::Framebuffer(_) => 0u8, RawBuffer::Iterations(_) => 1u8 };
+    out.write_all(RAW_MAGIC)?;
+    out.write_all(&RAW_VERSION.to_le_bytes())?;
+    out.write_all(&[dtype])?;
+    out.write_all(&(width as u32).to_le_bytes())?;
+    out.write_all(&(height as u32).to_le_bytes())?;
+    for v in &[view.upper_left.re, view.upper_left.im, view.lower_right.re, view.lower_right.im] {
+        out.write_all(&v.to_bits().to_le_bytes())?;
+    }
+    match *buffer {
+        RawBuffer::Framebuffer(ref pixels) => for p in pixels { out.write_all(&p.to_le_bytes())?; },
+        RawBuffer::Iterations(ref values)  => for v in values { out.write_all(&v.to_bits().to_le_bytes())?; },
+    }
+    out.flush()
+}
+
+fn read_u32(input: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut dyn Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(f64::from_bits(u64::from_le_bytes(bytes)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn import_raw(path: &str) -> io::Result<(RawBuffer, usize, usize, View)> {
+    let file       = File::open(path)?;
+    let length     = file.metadata()?.len();
+    let mut input  = BufReader::new(file);
+    let mut header = [0; 7];
+    input.read_exact(&mut header)?;
+    if &header[.. 4] != RAW_MAGIC {
+        return Err(invalid_data(format!("{} is not a raw export", path)));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != RAW_VERSION {
+        return Err(invalid_data(format!("unsupported raw export version {}", version)));
+    }
+    let width  = read_u32(&mut input)? as usize;
+    let height = read_u32(&mut input)? as usize;
+    let view   = View {
+        upper_left:  Complex { re: read_f64(&mut input)?, im: read_f64(&mut input)? },
+        lower_right: Complex { re: read_f64(&mut input)?, im: read_f64(&mut input)? },
+    };
+    // A corrupt header must not size the allocation, so the sample count is
+    // checked against what the file actually holds first.
+    let len = width.checked_mul(height)
+        .filter(|&len| (len as u64).checked_mul(4).and_then(|bytes| bytes.checked_add(RAW_HEADER)) == Some(length))
+        .ok_or_else(|| {
+            invalid_data(format!("{} holds {} bytes, not a {}x{} raw export", path, length, width, height))
+        })?;
+    let mut samples = Vec::with_capacity(len);
+    for _ in 0 .. len {
+        samples.push(read_u32(&mut input)?);
+    }
+    let buffer = match header[6] {
+        0     => RawBuffer::Framebuffer(samples),
+        1     => RawBuffer::Iterations(samples.into_iter().map(f32::from_bits).collect()),
+        dtype => return Err(invalid_data(format!("unknown raw dtype {}", dtype))),
+    };
+    Ok((buffer, width, height, view))
+}
+
+pub fn export_view_raw(config: &Config, path: &str, iterations: bool) -> io::Result<()> {
+    let mut renderer = RendererBuilder::new().config(config).build();
+    let view         = fit_bounds_to_aspect(INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right,
+                                            config.width, config.height);
+    let buffer = if iterations {
+        renderer.iterate(view, (0.0, 0.0), &CancelToken::new());
+        RawBuffer::Iterations(renderer.cache().values())
+    } else {
+        RawBuffer::Framebuffer(renderer.render(view))
+    };
+    export_raw(path, &buffer, config.width, config.height, view)?;
+    println!("Exported {}.", path);
+    Ok(())
+}
+
+// Turns a raw export back into a PNG, coloring iteration exports with the
+// default palette at the default gamma.
+pub fn raw_to_png(raw_path: &str, png_path: &str) -> Result<(), Box<dyn Error>> {
+    let (buffer, width, height, view) = import_raw(raw_path)?;
+    let pixels = match buffer {
+        RawBuffer::Framebuffer(pixels) => pixels,
+        RawBuffer::Iterations(values)  => {
+            let palette = PaletteLut::new(COLORS, DEFAULT_GAMMA);
+            values.iter().map(|&v| palette.color(v)).collect()
+        },
+    };
+    save_png(&pixels, width, height, png_path)?;
+    println!("Wrote {} ({} - {}, view {}{:+}i .. {}{:+}i).", png_path, width, height,
+             view.upper_left.re, view.upper_left.im, view.lower_right.re, view.lower_right.im);
+    Ok(())
+}
+
+// Side of the square region level 0 of a pyramid covers: the initial view's
+// width, so the whole set fits in the root tile.
+const PYRAMID_EXTENT: f64 = INITIAL_VIEW.lower_right.re - INITIAL_VIEW.upper_left.re;
+
+// Writes <dir>/<level>/<x>/<y>.png for levels 0 ..= max_level, level L being
+// 2^L x 2^L tiles over the same square around `center`. Like tile_manifest,
+// each tile's corners come from pixel_to_point over the whole level, so
+// neighbouring tiles share exact edges at every level.
+pub fn export_pyramid(config:    &Config,
+                      dir:       &str,
+                      center:    Complex<f64>,
+                      max_level: u32,
+                      tile_size: usize) -> Result<(), Box<dyn Error>> {
+    let half   = PYRAMID_EXTENT / 2.0;
+    let region = View {
+        upper_left:  Complex {re: center.re - half, im: center.im + half},
+        lower_right: Complex {re: center.re + half, im: center.im - half},
+    };
+    let mut renderer = tile_builder(config, tile_size, tile_size, frame_limit(config, region)).build();
+    for level in 0 ..= max_level {
+        let tiles  = 1usize << level;
+        let bounds = (tiles * tile_size, tiles * tile_size);
+        for x in 0 .. tiles {
+            let column_dir = format!("{}/{}/{}", dir, level, x);
+            fs::create_dir_all(&column_dir)?;
+            for y in 0 .. tiles {
+                let view = View {
+                    upper_left:  pixel_to_point(bounds, (x * tile_size, y * tile_size),
+                                                region.upper_left, region.lower_right, 1.0, 0.0),
+                    lower_right: pixel_to_point(bounds, ((x + 1) * tile_size, (y + 1) * tile_size),
+                                                region.upper_left, region.lower_right, 1.0, 0.0),
+                };
+                save_png(&renderer.render(view), tile_size, tile_size, &format!("{}/{}.png", column_dir, y))?;
+            }
+        }
+        println!("Wrote pyramid level {} ({} x {} tiles).", level, tiles, tiles);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exr_holds_linear_colors_and_iterations() {
+        use exr::prelude::*;
+        let path    = std::env::temp_dir().join(format!("mandelbrot_test_{}.exr", std::process::id()));
+        let path    = path.to_str().unwrap();
+        let samples = vec![[0.25, 0.5, 1.0, 17.5]; 4 * 3];
+        export_exr(&samples, 4, 3, path).unwrap();
+        let image   = read_all_flat_layers_from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+        let channels = &image.layer_data[0].channel_data.list;
+        let value    = |name: &str| {
+            let channel = channels.iter().find(|c| c.name.eq(name)).expect(name);
+            match channel.sample_data {
+                FlatSamples::F32(ref values) => values[0],
+                _                            => panic!("{} is not f32", name),
+            }
+        };
+        assert_eq!((value("R"), value("G"), value("B"), value("iterations")), (0.25, 0.5, 1.0, 17.5));
+    }
+
+    #[test]
+    fn raw_round_trip_keeps_view_and_rejects_bad_sizes() {
+        let path   = std::env::temp_dir().join(format!("mandelbrot_test_{}.raw", std::process::id()));
+        let path   = path.to_str().unwrap();
+        let view   = View { upper_left:  Complex { re: -0.7445, im: 0.1320 },
+                            lower_right: Complex { re: -0.7421, im: 0.1304 } };
+        let values = vec![0.5, 1.25, 7.0, 255.0, 3.5, 0.0];
+        export_raw(path, &RawBuffer::Iterations(values.clone()), 3, 2, view).unwrap();
+        let (buffer, width, height, read) = import_raw(path).unwrap();
+        assert_eq!((width, height), (3, 2));
+        assert_eq!((read.upper_left, read.lower_right), (view.upper_left, view.lower_right));
+        match buffer {
+            RawBuffer::Iterations(read) => assert_eq!(read, values),
+            RawBuffer::Framebuffer(_)   => panic!("dtype changed on the way"),
+        }
+
+        // A header claiming more samples than the file holds.
+        export_raw(path, &RawBuffer::Framebuffer(vec![0; 6]), 0x10000, 0x10000, view).unwrap();
+        let error = import_raw(path).err().expect("oversized header was accepted");
+        fs::remove_file(path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn annotation_counts_come_from_the_frame() {
+        let config     = Config { width: 48, height: 32, limit: 64, ..Config::default() };
+        let view       = View { upper_left:  Complex { re: -0.21, im: 0.005 },
+                                lower_right: Complex { re: -0.19, im: -0.005 } };
+        let mut render = RendererBuilder::new().config(&config).build();
+        render.render(view);
+        assert!(render.active_limit() > config.limit);
+        let label      = |point| annotation_text(&render, view, 1.0, &Annotation { point, position: (0, 0), color: 0 });
+        // The center lies inside the cardioid, so it ran to the frame's limit.
+        assert!(label(None).starts_with(&format!("n={} ", render.active_limit())), "{}", label(None));
+        assert!(label(Some(Complex { re: 1.0, im: 0.0 })).starts_with("n=- "));
+    }
+
+    #[test]
+    fn manifest_tiles_use_full_frame_limit() {
+        let config   = Config { width: 48, height: 32, limit: 64, ..Config::default() };
+        let view     = View { upper_left:  Complex { re: -0.7445, im: 0.1320 },
+                              lower_right: Complex { re: -0.7421, im: 0.1304 } };
+        let mut full = RendererBuilder::new().config(&config).size(96, 64).build();
+        full.iterate(view, (0.0, 0.0), &CancelToken::new());
+        assert!(full.active_limit() > config.limit);
+        for tile in &tile_manifest(&config, view, 2, 2).tiles {
+            let view         = View { upper_left:  Complex { re: tile.upper_left.0,  im: tile.upper_left.1 },
+                                      lower_right: Complex { re: tile.lower_right.0, im: tile.lower_right.1 } };
+            let mut renderer = tile_builder(&config, tile.width, tile.height, tile.limit)
+                .window(tile.frame, tile.origin).build();
+            renderer.iterate(view, (0.0, 0.0), &CancelToken::new());
+            assert_eq!(tile.limit, full.active_limit());
+            assert_eq!(renderer.active_limit(), full.active_limit());
+        }
+    }
+
+    #[test]
+    fn manifest_tiles_stitch_into_the_full_frame() {
+        let config   = Config { width: 48, height: 32, limit: 64, ..Config::default() };
+        let view     = View { upper_left:  Complex { re: -0.7445, im: 0.1320 },
+                              lower_right: Complex { re: -0.7421, im: 0.1304 } };
+        let manifest = tile_manifest(&config, view, 2, 2);
+        let frame    = tile_builder(&config, 96, 64, frame_limit(&config, view)).build().render(view);
+        let mut stitched = vec![0; 96 * 64];
+        for (index, tile) in manifest.tiles.iter().enumerate() {
+            let (column, row) = (index % manifest.columns, index / manifest.columns);
+            for (y, line) in tile_pixels(&config, tile).chunks(tile.width).enumerate() {
+                let start = (row * tile.height + y) * 96 + column * tile.width;
+                stitched[start .. start + tile.width].copy_from_slice(line);
+            }
+        }
+        let differ = frame.iter().zip(&stitched).filter(|&(a, b)| a != b).count();
+        assert_eq!(differ, 0, "{} of {} pixels differ from the full frame", differ, frame.len());
+    }
+}
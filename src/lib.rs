@@ -0,0 +1,3447 @@
+//! Headless Mandelbrot, Julia and Burning Ship renderer behind the
+//! interactive viewer in main.rs. `render_mandelbrot` covers the common case;
+//! `RendererBuilder` exposes every other setting.
+
+#![feature(portable_simd)]
+#![cfg_attr(test, feature(test))]
+// Edition 2015 spells out 'static on consts, the escape tests are negated so a
+// NaN counts as escaped, and clamps go through max/min so a NaN drops out.
+#![allow(clippy::redundant_static_lifetimes, clippy::neg_cmp_op_on_partial_ord, clippy::manual_clamp,
+         clippy::too_many_arguments, clippy::needless_range_loop, clippy::type_complexity)]
+
+extern crate num;
+extern crate crossbeam;
+#[cfg(test)]
+extern crate test;
+
+use crossbeam::sync::SegQueue;
+use num::{BigInt, Complex};
+use num::traits::{Float, FromPrimitive, Signed, ToPrimitive};
+use std::cmp;
+use std::f32;
+use std::f64;
+use std::mem;
+use std::ops::{Add, Mul, Sub};
+use std::simd::{f32x4, u32x4, Select, StdFloat};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use std::simd::{f32x8, u32x8};
+use std::simd::cmp::{SimdPartialEq, SimdPartialOrd};
+use std::simd::num::SimdFloat;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+pub const COLORS: &'static [(f32, f32, f32)] = &[(0.0,    7.0,    100.0),
+                                             (32.0,   107.0,  203.0),
+                                             (237.0,  255.0,  255.0),
+                                             (255.0,  170.0,  0.0),
+                                             (0.0,    2.0,    0.0)];
+
+// Named palettes the viewer cycles through; the first is COLORS. Names are
+// NUL-terminated for imgui.
+pub const PALETTES: &'static [(&'static str, &'static [(f32, f32, f32)])] = &[
+    ("Ultra Fractal\0", COLORS),
+    ("Grayscale\0",     &[(0.0,    0.0,    0.0),
+                          (255.0,  255.0,  255.0)]),
+    ("Fire\0",          &[(0.0,    0.0,    0.0),
+                          (128.0,  0.0,    0.0),
+                          (255.0,  80.0,   0.0),
+                          (255.0,  200.0,  0.0),
+                          (255.0,  255.0,  200.0)]),
+    ("Ocean\0",         &[(0.0,    10.0,   40.0),
+                          (0.0,    60.0,   120.0),
+                          (0.0,    140.0,  180.0),
+                          (120.0,  220.0,  230.0),
+                          (240.0,  255.0,  255.0)]),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KernelWidth {
+    Scalar,
+    X4,
+    X8,
+}
+
+impl KernelWidth {
+    pub fn name(self) -> &'static str {
+        match self {
+            KernelWidth::Scalar => "scalar\0",
+            KernelWidth::X4     => "x4\0",
+            KernelWidth::X8     => "x8\0",
+        }
+    }
+
+    pub fn supported(self) -> bool {
+        match self {
+            KernelWidth::Scalar => true,
+            KernelWidth::X4     => true,
+            KernelWidth::X8     => has_avx2(),
+        }
+    }
+
+    // Widest kernel this CPU can run, checked at runtime so one binary picks
+    // X8 on AVX2 machines and X4 everywhere else.
+    pub fn fastest() -> KernelWidth {
+        if KernelWidth::X8.supported() { KernelWidth::X8 } else { KernelWidth::X4 }
+    }
+
+    // Instruction set the kernel runs on, for the startup banner.
+    pub fn isa(self) -> &'static str {
+        match self {
+            KernelWidth::Scalar => "scalar",
+            KernelWidth::X4 if cfg!(any(target_arch = "x86", target_arch = "x86_64")) => "SSE2",
+            KernelWidth::X4     => "128-bit SIMD",
+            KernelWidth::X8     => "AVX2",
+        }
+    }
+
+    pub fn next(self) -> KernelWidth {
+        let next = match self {
+            KernelWidth::Scalar => KernelWidth::X4,
+            KernelWidth::X4     => KernelWidth::X8,
+            KernelWidth::X8     => KernelWidth::Scalar,
+        };
+        if next.supported() { next } else { next.next() }
+    }
+}
+
+// Raw skips the palette and writes the iteration count as gray, so timing it
+// against Palette separates kernel throughput from coloring cost. Histogram
+// spreads the palette by how many pixels reach each count rather than by the
+// count itself. OrbitTrap colors every pixel, interior included, by how close
+// its orbit comes to `shape`. Distance lights up the exterior by its estimated
+// distance to the set, so filaments thinner than a pixel still show.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColoringMode {
+    Palette,
+    Raw,
+    Histogram,
+    OrbitTrap { shape: TrapShape },
+    Distance,
+}
+
+impl ColoringMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColoringMode::Palette   => "palette\0",
+            ColoringMode::Raw       => "raw\0",
+            ColoringMode::Histogram => "histogram\0",
+            ColoringMode::OrbitTrap { shape: TrapShape::Point }  => "trap:point\0",
+            ColoringMode::OrbitTrap { shape: TrapShape::Line }   => "trap:line\0",
+            ColoringMode::OrbitTrap { shape: TrapShape::Circle } => "trap:circle\0",
+            ColoringMode::Distance  => "distance\0",
+        }
+    }
+
+    pub fn next(self) -> ColoringMode {
+        match self {
+            ColoringMode::Palette   => ColoringMode::Raw,
+            ColoringMode::Raw       => ColoringMode::Histogram,
+            ColoringMode::Histogram => ColoringMode::OrbitTrap { shape: TrapShape::Point },
+            ColoringMode::OrbitTrap { shape: TrapShape::Point }  => ColoringMode::OrbitTrap { shape: TrapShape::Line },
+            ColoringMode::OrbitTrap { shape: TrapShape::Line }   => ColoringMode::OrbitTrap { shape: TrapShape::Circle },
+            ColoringMode::OrbitTrap { shape: TrapShape::Circle } => ColoringMode::Distance,
+            ColoringMode::Distance  => ColoringMode::Palette,
+        }
+    }
+
+    pub fn cell_value(self) -> CellValue {
+        match self {
+            ColoringMode::OrbitTrap { shape } => CellValue::Trap(shape),
+            ColoringMode::Distance            => CellValue::Distance,
+            _                                 => CellValue::Escape,
+        }
+    }
+
+    // Squared escape radius every kernel iterates to, through
+    // RenderSettings::bailout. The smooth fraction and the distance estimate
+    // are only exact far from the set; Raw counts and the traps want the
+    // plain radius of 2.
+    pub fn bailout(self) -> f32 {
+        match self {
+            ColoringMode::Palette | ColoringMode::Histogram => SMOOTH_BAILOUT,
+            ColoringMode::Distance                          => DISTANCE_BAILOUT,
+            _                                               => ESCAPE_BAILOUT,
+        }
+    }
+}
+
+pub const ESCAPE_BAILOUT: f32 = 4.0;
+
+// Radius 2^16, where the smooth fraction no longer shows bands.
+const SMOOTH_BAILOUT: f32 = 65536.0 * 65536.0;
+
+// What the cache keeps next to each count: the escape fraction, the trap
+// distance for OrbitTrap or the boundary distance for Distance.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CellValue {
+    Escape,
+    Trap(TrapShape),
+    Distance,
+}
+
+// Point is the origin, Line the real axis and Circle the unit circle.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrapShape {
+    Point,
+    Line,
+    Circle,
+}
+
+impl TrapShape {
+    // Distance of z = x + iy from the shape, with |z|^2 already at hand.
+    #[inline]
+    fn distance(self, y: f32x4, magnitude: f32x4) -> f32x4 {
+        match self {
+            TrapShape::Point  => magnitude.sqrt(),
+            TrapShape::Line   => abs_x4(y),
+            TrapShape::Circle => abs_x4(magnitude.sqrt() - f32x4::splat(1.0)),
+        }
+    }
+}
+
+// Mandelbrot iterates from z = 0 with c at the pixel; Julia starts z at the
+// pixel and keeps c fixed. Burning Ship is Mandelbrot with |re z| and |im z|
+// folded before each squaring.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FractalKind {
+    Mandelbrot,
+    Julia { c: Complex<f64> },
+    BurningShip,
+}
+
+impl FractalKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FractalKind::Mandelbrot  => "Mandelbrot\0",
+            FractalKind::Julia {..}  => "Julia\0",
+            FractalKind::BurningShip => "Burning Ship\0",
+        }
+    }
+
+    pub fn next(self) -> FractalKind {
+        match self {
+            FractalKind::Mandelbrot  => FractalKind::Julia { c: DEFAULT_JULIA_C },
+            FractalKind::Julia {..}  => FractalKind::BurningShip,
+            FractalKind::BurningShip => FractalKind::Mandelbrot,
+        }
+    }
+
+    // The fixed c in f32, or None when c is the sampled point.
+    pub fn julia_c(self) -> Option<(f32, f32)> {
+        match self {
+            FractalKind::Julia { c } => Some((c.re as f32, c.im as f32)),
+            _                        => None,
+        }
+    }
+
+    // Where each kind is worth looking at first. All share the width of
+    // INITIAL_VIEW, so the overlay's zoom factor means the same for each.
+    pub fn initial_view(self) -> View {
+        match self {
+            FractalKind::Mandelbrot  => INITIAL_VIEW,
+            FractalKind::Julia {..}  => View {
+                upper_left:  Complex {re: -1.7, im:  1.0},
+                lower_right: Complex {re:  1.7, im: -1.0},
+            },
+            // The folded imaginary part pushes the ship below the real axis.
+            FractalKind::BurningShip => View {
+                upper_left:  Complex {re: -2.2, im:  0.5},
+                lower_right: Complex {re:  1.2, im: -1.5},
+            },
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_avx2() -> bool {
+    is_x86_feature_detected!("avx") && is_x86_feature_detected!("avx2")
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn has_avx2() -> bool {
+    false
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct View {
+    pub upper_left:  Complex<f64>,
+    pub lower_right: Complex<f64>,
+}
+
+pub const INITIAL_VIEW: View = View {
+    upper_left:  Complex {re: -2.2, im:  1.0},
+    lower_right: Complex {re:  1.2, im: -1.0},
+};
+
+// Resolution, iteration limit, worker count and supersampling factor the run
+// starts with. The viewer builds it once from its command line.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub width:          usize,
+    pub height:         usize,
+    pub limit:          u32,
+    pub num_threads:    usize,
+    // Samples per pixel edge in the interactive view; see SUPERSAMPLE_FACTORS.
+    pub supersample:    usize,
+    // Display gamma palette colors are encoded for; 1.0 leaves them as is.
+    pub gamma:          f32,
+    // See RenderSettings::reference_bits.
+    pub reference_bits: u32,
+    // Filter the supersampled EXR export is downsampled with.
+    pub export_filter:  DownsampleFilter,
+}
+
+// Supersampling factors the interactive view cycles through.
+pub const SUPERSAMPLE_FACTORS: &'static [usize] = &[1, 2, 4];
+
+pub fn next_supersample(factor: usize) -> usize {
+    let index = SUPERSAMPLE_FACTORS.iter().position(|&f| f == factor).map(|i| i + 1).unwrap_or(0);
+    SUPERSAMPLE_FACTORS[index % SUPERSAMPLE_FACTORS.len()]
+}
+
+// Workers when the machine's parallelism can't be queried.
+const FALLBACK_THREADS: usize = 4;
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            width:          1024,
+            height:         768,
+            limit:          100,
+            num_threads:    thread::available_parallelism().map(|n| n.get()).unwrap_or(FALLBACK_THREADS),
+            supersample:    1,
+            gamma:          DEFAULT_GAMMA,
+            reference_bits: 0,
+            export_filter:  DownsampleFilter::Lanczos,
+        }
+    }
+}
+
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RenderSettings {
+    pub limit:            u32,
+    pub threads:          usize,
+    pub kernel:           KernelWidth,
+    pub pixel_aspect:     f64,
+    // Radians, counter-clockwise in the complex plane.
+    pub rotation:         f64,
+    // Seeds the sub-pixel offsets of accumulation frames, see taa_jitter.
+    pub jitter_seed:      u64,
+    // Edge of the square tiles workers pull; 0 uses TILE_SIZE.
+    pub tile_size:        usize,
+    pub fractal:          FractalKind,
+    // None runs the native z*z + c kernels.
+    pub formula:          Option<Arc<Formula>>,
+    // Iterations added to `limit` for every halving of the view width
+    // below INITIAL_VIEW's; 0 keeps the limit fixed.
+    pub limit_per_octave: u32,
+    // Set from the coloring, see CellValue.
+    pub cell_value:       CellValue,
+    // Squared escape radius of every kernel, set from the coloring too.
+    pub bailout:          f32,
+    // Fraction bits of the perturbation reference orbit; 0 computes it in
+    // DoubleDouble. Glitched pixels fall back to DoubleDouble.
+    pub reference_bits:   u32,
+    // When set, views are offsets from this point, which seeds the reference
+    // orbit at its own precision; for views deeper than f64 corners place.
+    pub center:           Option<Arc<PrecisePoint>>,
+    // Set when the render is one window of a larger frame of the view.
+    pub window:           Option<FrameWindow>,
+}
+
+// The part of a `frame` sized render of the view a renderer covers, its top
+// left pixel at `origin`; both are in output pixels. Samples are placed from
+// the frame's corners exactly as in a render of the whole frame, so windows
+// rendered apart stitch into that render pixel for pixel.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameWindow {
+    pub frame:  (usize, usize),
+    pub origin: (usize, usize),
+}
+
+impl FrameWindow {
+    fn scaled(self, factor: usize) -> FrameWindow {
+        FrameWindow {
+            frame:  (self.frame.0 * factor, self.frame.1 * factor),
+            origin: (self.origin.0 * factor, self.origin.1 * factor),
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            limit:            Config::default().limit,
+            threads:          Config::default().num_threads,
+            kernel:           KernelWidth::fastest(),
+            pixel_aspect:     1.0,
+            rotation:         0.0,
+            jitter_seed:      DEFAULT_JITTER_SEED,
+            tile_size:        0,
+            fractal:          FractalKind::Mandelbrot,
+            formula:          None,
+            limit_per_octave: DEFAULT_LIMIT_PER_OCTAVE,
+            cell_value:       CellValue::Escape,
+            bailout:          ESCAPE_BAILOUT,
+            reference_bits:   0,
+            center:           None,
+            window:           None,
+        }
+    }
+}
+
+// Keeps FixedPoint products well inside what the f64 rounding can scale.
+pub const MAX_REFERENCE_BITS: u32 = 2048;
+
+// Enough that the boundary keeps its filaments as detail shrinks with each
+// zoom step, without slowing the shallow views.
+pub const DEFAULT_LIMIT_PER_OCTAVE: u32 = 16;
+
+impl RenderSettings {
+    /// Iteration limit for a view with these corners: `limit`, plus
+    /// `limit_per_octave` for each halving of the width below INITIAL_VIEW's.
+    pub fn limit_at(&self, upper_left: Complex<f64>, lower_right: Complex<f64>) -> u32 {
+        let initial = INITIAL_VIEW.lower_right.re - INITIAL_VIEW.upper_left.re;
+        let octaves = (initial / (lower_right.re - upper_left.re).abs()).log2().max(0.0);
+        self.limit.saturating_add((self.limit_per_octave as f64 * octaves).min(u32::MAX as f64) as u32)
+    }
+}
+
+// Shared between a render and whoever may stop it. Renders also count the
+// pixels they finish here, so the other side can report progress.
+#[derive(Clone)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    pixels:    Arc<AtomicUsize>,
+}
+
+impl Default for CancelToken {
+    fn default() -> CancelToken {
+        CancelToken::new()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken { cancelled: Arc::new(AtomicBool::new(false)), pixels: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn finish_pixels(&self, count: usize) {
+        self.pixels.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn pixels_done(&self) -> usize {
+        self.pixels.load(Ordering::Relaxed)
+    }
+}
+
+// Keeps the real span and center and sets the imaginary span to match a
+// `width` x `height` image, so the plane maps onto square pixels.
+pub fn fit_bounds_to_aspect(upper_left: Complex<f64>, lower_right: Complex<f64>, width: usize, height: usize) -> View {
+    let center = view_center(View { upper_left, lower_right });
+    let half_h = (lower_right.re - upper_left.re) * height as f64 / cmp::max(width, 1) as f64 / 2.0;
+    View {
+        upper_left:  Complex {re: upper_left.re,  im: center.im + half_h},
+        lower_right: Complex {re: lower_right.re, im: center.im - half_h},
+    }
+}
+
+// `pixel_aspect` is pixel width over pixel height; the horizontal extent is
+// stretched by it around the view center, so 1.0 is the plain mapping.
+// `rotation` turns the sampled rectangle by that many radians around the view
+// center. Its corners then reach outside the unrotated view, which is what
+// keeps the rectangle's shape and therefore the aspect.
+pub fn pixel_to_point(bounds:       (usize, usize),
+                      pixel:        (usize, usize),
+                      upper_left:   Complex<f64>,
+                      lower_right:  Complex<f64>,
+                      pixel_aspect: f64,
+                      rotation:     f64) -> Complex<f64> {
+
+    let (width, height) = (lower_right.re - upper_left.re,
+                           upper_left.im  - lower_right.im);
+    let column = pixel.0 as f64 * pixel_aspect + (1.0 - pixel_aspect) * bounds.0 as f64 / 2.0;
+    let point  = Complex {
+        re: upper_left.re + column         * width  / bounds.0 as f64,
+        im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64
+    };
+    if rotation == 0.0 {
+        return point;
+    }
+    let center     = Complex { re: upper_left.re + width / 2.0, im: upper_left.im - height / 2.0 };
+    let (sin, cos) = rotation.sin_cos();
+    let (dx, dy)   = (point.re - center.re, point.im - center.im);
+    Complex { re: center.re + dx * cos - dy * sin, im: center.im + dx * sin + dy * cos }
+}
+
+// pixel_to_point minus the view center, computed without ever forming the
+// absolute point, so it stays exact however small the view gets.
+fn pixel_offset(bounds:       (usize, usize),
+                pixel:        (usize, usize),
+                upper_left:   Complex<f64>,
+                lower_right:  Complex<f64>,
+                pixel_aspect: f64,
+                rotation:     f64) -> Complex<f64> {
+
+    let (width, height) = (lower_right.re - upper_left.re,
+                           upper_left.im  - lower_right.im);
+    let column     = pixel.0 as f64 * pixel_aspect + (1.0 - pixel_aspect) * bounds.0 as f64 / 2.0;
+    let (dx, dy)   = ((column / bounds.0 as f64 - 0.5) * width,
+                      (0.5 - pixel.1 as f64 / bounds.1 as f64) * height);
+    let (sin, cos) = rotation.sin_cos();
+    Complex { re: dx * cos - dy * sin, im: dx * sin + dy * cos }
+}
+
+// Inverse of pixel_to_point for the same view parameters, returning
+// fractional pixel coordinates.
+pub fn point_to_pixel(bounds:       (usize, usize),
+                      point:        Complex<f64>,
+                      upper_left:   Complex<f64>,
+                      lower_right:  Complex<f64>,
+                      pixel_aspect: f64,
+                      rotation:     f64) -> (f64, f64) {
+
+    let (width, height) = (lower_right.re - upper_left.re,
+                           upper_left.im  - lower_right.im);
+    let center     = Complex { re: upper_left.re + width / 2.0, im: upper_left.im - height / 2.0 };
+    let (sin, cos) = (-rotation).sin_cos();
+    let (dx, dy)   = (point.re - center.re, point.im - center.im);
+    let (re, im)   = (center.re + dx * cos - dy * sin, center.im + dx * sin + dy * cos);
+    let column     = (re - upper_left.re) * bounds.0 as f64 / width;
+    ((column - (1.0 - pixel_aspect) * bounds.0 as f64 / 2.0) / pixel_aspect,
+     (upper_left.im - im) * bounds.1 as f64 / height)
+}
+
+// How close, in each coordinate, an orbit must return to its snapshot to
+// count as a cycle.
+const PERIODICITY_TOLERANCE: f32 = 1e-6;
+
+#[inline(never)]
+// Returns the escape count and |z|^2 at the escape per lane; lanes that never
+// escape report a magnitude of 0.
+fn mandelbrot_vector(z_x: f32x4, z_y: f32x4, c_x: f32x4, c_y: f32x4, max_iter: u32, bailout: f32) -> (u32x4, f32x4) {
+    // Lanes starting at z = c continue the orbit of 0, which never escapes for
+    // a c in the main cardioid or the period-2 bulb; they skip to max_iter.
+    // So do lanes whose orbit comes back to a snapshot taken at each power of
+    // two iterations, since a cycle never escapes either.
+    let (q_x, q_y) = (c_x - f32x4::splat(0.25), c_y * c_y);
+    let q          = q_x * q_x + q_y;
+    let bulb       = c_x + f32x4::splat(1.0);
+    let mut inside = ((q * (q + q_x)).simd_lt(q_y * f32x4::splat(0.25))
+                      | (bulb * bulb + q_y).simd_lt(f32x4::splat(0.0625)))
+                     & z_x.simd_eq(c_x) & z_y.simd_eq(c_y);
+    let mut x = z_x;
+    let mut y = z_y;
+    let mut count     = u32x4::splat(0);
+    let mut magnitude = f32x4::splat(0.0);
+    let tolerance     = f32x4::splat(PERIODICITY_TOLERANCE);
+    let (mut snapshot_x, mut snapshot_y, mut snapshot) = (x, y, 1);
+    for i in 0..max_iter as usize {
+        let xy = x * y;
+        let xx = x * x;
+        let yy = y * y;
+        let sum = xx + yy;
+        let mask = sum.simd_lt(f32x4::splat(bailout)) & !inside;
+        // Any escaped magnitude is at least the bailout, so 0 means not recorded yet.
+        magnitude = (mask | inside | magnitude.simd_gt(f32x4::splat(0.0))).select(magnitude, sum);
+        if !mask.any() { break }
+        count += mask.select(u32x4::splat(1), u32x4::splat(0));
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+        inside |= mask & abs_x4(x - snapshot_x).simd_lt(tolerance) & abs_x4(y - snapshot_y).simd_lt(tolerance);
+        if i + 1 == snapshot {
+            snapshot_x = x;
+            snapshot_y = y;
+            snapshot  *= 2;
+        }
+    }
+    (inside.select(u32x4::splat(max_iter), count), magnitude)
+}
+
+// Same vectorized loop as the Mandelbrot kernel with c broadcast to all lanes.
+#[inline(never)]
+fn julia_vector(z_x: f32x4, z_y: f32x4, c: Complex<f32>, max_iter: u32, bailout: f32) -> (u32x4, f32x4) {
+    mandelbrot_vector(z_x, z_y, f32x4::splat(c.re), f32x4::splat(c.im), max_iter, bailout)
+}
+
+// Clears the sign bits, the SIMD |v|.
+#[inline]
+fn abs_x4(v: f32x4) -> f32x4 {
+    unsafe { mem::transmute(mem::transmute::<f32x4, u32x4>(v) & u32x4::splat(0x7FFF_FFFF)) }
+}
+
+// Burning Ship from z = 0, with the same count and escape magnitude as
+// mandelbrot_vector.
+#[inline(never)]
+fn burning_ship_vector(c_x: f32x4, c_y: f32x4, max_iter: u32, bailout: f32) -> (u32x4, f32x4) {
+    let mut x = f32x4::splat(0.0);
+    let mut y = f32x4::splat(0.0);
+    let mut count     = u32x4::splat(0);
+    let mut magnitude = f32x4::splat(0.0);
+    for _ in 0..max_iter as usize {
+        let xx = x * x;
+        let yy = y * y;
+        let sum = xx + yy;
+        let mask = sum.simd_lt(f32x4::splat(bailout));
+        magnitude = (mask | magnitude.simd_gt(f32x4::splat(0.0))).select(magnitude, sum);
+        if !mask.any() { break }
+        count += mask.select(u32x4::splat(1), u32x4::splat(0));
+        let xy = abs_x4(x) * abs_x4(y);
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude)
+}
+
+// Runs the Mandelbrot loop, or the Burning Ship one when `fold` is set, and
+// returns the escape count with the closest each lane's orbit came to
+// `shape`. Like the others, Mandelbrot lanes start at z = c.
+#[inline(never)]
+fn orbit_trap_vector(z_x:      f32x4,
+                     z_y:      f32x4,
+                     c_x:      f32x4,
+                     c_y:      f32x4,
+                     max_iter: u32,
+                     shape:    TrapShape,
+                     fold:     bool,
+                     bailout:  f32) -> (u32x4, f32x4) {
+    let mut x = z_x;
+    let mut y = z_y;
+    let mut count   = u32x4::splat(0);
+    let mut nearest = f32x4::splat(f32::INFINITY);
+    for _ in 0..max_iter as usize {
+        let xx = x * x;
+        let yy = y * y;
+        let sum = xx + yy;
+        let mask = sum.simd_lt(f32x4::splat(bailout));
+        if !mask.any() { break }
+        nearest = mask.select(nearest.simd_min(shape.distance(y, sum)), nearest);
+        count += mask.select(u32x4::splat(1), u32x4::splat(0));
+        let xy = if fold { abs_x4(x) * abs_x4(y) } else { x * y };
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, nearest)
+}
+
+// Escape radius squared for distance estimates, far past 2 so log|z| at the
+// escape is accurate.
+const DISTANCE_BAILOUT: f32 = 1e8;
+
+// Carries the derivative dz/dc (dz/dz0 for Julia, where `dc` is 0) next to z
+// and returns the count with |z|^2 and |dz|^2 at the escape; lanes that never
+// escape report a magnitude of 0. The Burning Ship fold ignores the sign
+// flips in the derivative, which keeps the estimate close enough to draw.
+#[inline(never)]
+fn distance_vector(z_x:      f32x4,
+                   z_y:      f32x4,
+                   c_x:      f32x4,
+                   c_y:      f32x4,
+                   max_iter: u32,
+                   dc:       f32,
+                   fold:     bool,
+                   bailout:  f32) -> (u32x4, f32x4, f32x4) {
+    let mut x  = z_x;
+    let mut y  = z_y;
+    let mut dx = f32x4::splat(1.0);
+    let mut dy = f32x4::splat(0.0);
+    let mut count      = u32x4::splat(0);
+    let mut magnitude  = f32x4::splat(0.0);
+    let mut derivative = f32x4::splat(0.0);
+    for _ in 0..max_iter as usize {
+        let xx = x * x;
+        let yy = y * y;
+        let sum = xx + yy;
+        let mask = sum.simd_lt(f32x4::splat(bailout));
+        let record = !mask & magnitude.simd_eq(f32x4::splat(0.0));
+        magnitude  = record.select(sum, magnitude);
+        derivative = record.select(dx * dx + dy * dy, derivative);
+        if !mask.any() { break }
+        count += mask.select(u32x4::splat(1), u32x4::splat(0));
+        let two = f32x4::splat(2.0);
+        let ddx = two * (x * dx - y * dy) + f32x4::splat(dc);
+        dy = two * (x * dy + y * dx);
+        dx = ddx;
+        let xy = if fold { abs_x4(x) * abs_x4(y) } else { x * y };
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude, derivative)
+}
+
+// One step of a user formula, compiled to postfix and evaluated on an operand
+// stack of four complex lanes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Z,
+    C,
+    Const(f32, f32),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    Pow(u32),
+    Sin,
+    Cos,
+    Exp,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(f32),
+    Name(String),
+    Symbol(char),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Formula {
+    ops: Vec<Op>,
+}
+
+type Lanes = (f32x4, f32x4);
+
+// Largest exponent accepted after `^`, which is unrolled into multiplies.
+const MAX_FORMULA_POWER: u32 = 16;
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars  = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&d) = chars.peek() {
+                if !(d.is_ascii_digit() || d == '.') { break }
+                number.push(d);
+                chars.next();
+            }
+            tokens.push(Token::Number(number.parse().map_err(|_| format!("bad number '{}'", number))?));
+        } else if c.is_alphabetic() {
+            let mut name = String::new();
+            while let Some(&d) = chars.peek() {
+                if !d.is_alphanumeric() { break }
+                name.push(d);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+        } else if "+-*/^()".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over expr := term (+|- term)*, term := unary (*|/ unary)*,
+// unary := -unary | power, power := primary (^ integer)?
+struct FormulaParser<'a> {
+    tokens: &'a [Token],
+    pos:    usize,
+    ops:    Vec<Op>,
+}
+
+impl<'a> FormulaParser<'a> {
+    fn peek_symbol(&self, symbols: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(&Token::Symbol(c)) if symbols.contains(c) => Some(c),
+            _                                               => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        if self.peek_symbol(&symbol.to_string()).is_none() {
+            return Err(format!("expected '{}'", symbol));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<(), String> {
+        self.term()?;
+        while let Some(c) = self.peek_symbol("+-") {
+            self.pos += 1;
+            self.term()?;
+            self.ops.push(if c == '+' { Op::Add } else { Op::Sub });
+        }
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<(), String> {
+        self.unary()?;
+        while let Some(c) = self.peek_symbol("*/") {
+            self.pos += 1;
+            self.unary()?;
+            self.ops.push(if c == '*' { Op::Mul } else { Op::Div });
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<(), String> {
+        if self.peek_symbol("-").is_some() {
+            self.pos += 1;
+            self.unary()?;
+            self.ops.push(Op::Neg);
+            return Ok(());
+        }
+        self.primary()?;
+        if self.peek_symbol("^").is_some() {
+            self.pos += 1;
+            match self.tokens.get(self.pos) {
+                Some(&Token::Number(n)) if n.fract() == 0.0 && n <= MAX_FORMULA_POWER as f32 => {
+                    self.pos += 1;
+                    self.ops.push(Op::Pow(n as u32));
+                },
+                _ => return Err(format!("exponent must be an integer from 0 to {}", MAX_FORMULA_POWER)),
+            }
+        }
+        Ok(())
+    }
+
+    fn primary(&mut self) -> Result<(), String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| "unexpected end of formula".to_string())?;
+        self.pos += 1;
+        match token {
+            Token::Number(n)   => self.ops.push(Op::Const(n, 0.0)),
+            Token::Symbol('(') => {
+                self.expr()?;
+                self.expect(')')?;
+            },
+            Token::Name(ref name) => match name.as_str() {
+                "z" => self.ops.push(Op::Z),
+                "c" => self.ops.push(Op::C),
+                "i" => self.ops.push(Op::Const(0.0, 1.0)),
+                "sin" | "cos" | "exp" => {
+                    self.expect('(')?;
+                    self.expr()?;
+                    self.expect(')')?;
+                    self.ops.push(match name.as_str() { "sin" => Op::Sin, "cos" => Op::Cos, _ => Op::Exp });
+                },
+                _ => return Err(format!("unknown name '{}'", name)),
+            },
+            Token::Symbol(c) => return Err(format!("unexpected '{}'", c)),
+        }
+        Ok(())
+    }
+}
+
+fn lanes_mul(a: Lanes, b: Lanes) -> Lanes {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+// Transcendental functions have no SIMD form here, so they go lane by lane.
+fn per_lane(v: Lanes, f: fn(f32, f32) -> (f32, f32)) -> Lanes {
+    let mut out = v;
+    for k in 0 .. 4 {
+        let (re, im) = f(v.0[k], v.1[k]);
+        out.0[k] = re;
+        out.1[k] = im;
+    }
+    out
+}
+
+impl Formula {
+    pub fn parse(text: &str) -> Result<Formula, String> {
+        let tokens     = tokenize(text)?;
+        let mut parser = FormulaParser { tokens: &tokens, pos: 0, ops: Vec::new() };
+        parser.expr()?;
+        if parser.pos != tokens.len() {
+            return Err(format!("unexpected {:?}", tokens[parser.pos]));
+        }
+        Ok(Formula { ops: parser.ops })
+    }
+
+    // z*z + c has a hand-written kernel at every width, so it skips the
+    // interpreter.
+    pub fn is_quadratic(&self) -> bool {
+        self.ops == [Op::Z, Op::Z, Op::Mul, Op::C, Op::Add] || self.ops == [Op::Z, Op::Pow(2), Op::C, Op::Add]
+    }
+
+    fn eval(&self, z: Lanes, c: Lanes, stack: &mut Vec<Lanes>) -> Lanes {
+        let zero = f32x4::splat(0.0);
+        let one  = f32x4::splat(1.0);
+        stack.clear();
+        for op in &self.ops {
+            let value = match *op {
+                Op::Z              => z,
+                Op::C              => c,
+                Op::Const(re, im)  => (f32x4::splat(re), f32x4::splat(im)),
+                Op::Neg            => { let a = stack.pop().unwrap(); (zero - a.0, zero - a.1) },
+                Op::Pow(n)         => {
+                    let a = stack.pop().unwrap();
+                    (0 .. n).fold((one, zero), |acc, _| lanes_mul(acc, a))
+                },
+                Op::Sin            => per_lane(stack.pop().unwrap(), |re, im| (re.sin() * im.cosh(), re.cos() * im.sinh())),
+                Op::Cos            => per_lane(stack.pop().unwrap(), |re, im| (re.cos() * im.cosh(), -re.sin() * im.sinh())),
+                Op::Exp            => per_lane(stack.pop().unwrap(), |re, im| (re.exp() * im.cos(), re.exp() * im.sin())),
+                Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    match *op {
+                        Op::Add => (a.0 + b.0, a.1 + b.1),
+                        Op::Sub => (a.0 - b.0, a.1 - b.1),
+                        Op::Mul => lanes_mul(a, b),
+                        _       => {
+                            let denom = b.0 * b.0 + b.1 * b.1;
+                            ((a.0 * b.0 + a.1 * b.1) / denom, (a.1 * b.0 - a.0 * b.1) / denom)
+                        },
+                    }
+                },
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap()
+    }
+}
+
+fn mandelbrot_formula(formula: &Formula, z: Lanes, c_x: f32x4, c_y: f32x4, max_iter: u32, bailout: f32,
+                      stack: &mut Vec<Lanes>) -> u32x4 {
+    let mut z     = z;
+    let mut count = u32x4::splat(0);
+    for _ in 0..max_iter as usize {
+        let mask = (z.0 * z.0 + z.1 * z.1).simd_lt(f32x4::splat(bailout));
+        if !mask.any() { break }
+        count += mask.select(u32x4::splat(1), u32x4::splat(0));
+        z     = formula.eval(z, (c_x, c_y), stack);
+    }
+    count
+}
+
+#[inline(never)]
+pub fn mandelbrot_scalar(z_x: f32, z_y: f32, c_x: f32, c_y: f32, max_iter: u32, bailout: f32) -> (u32, f32) {
+    let mut x = z_x;
+    let mut y = z_y;
+    let mut count     = 0;
+    let mut magnitude = 0.0;
+    for _ in 0..max_iter {
+        let xy = x * y;
+        let xx = x * x;
+        let yy = y * y;
+        if !(xx + yy < bailout) {
+            magnitude = xx + yy;
+            break;
+        }
+        count += 1;
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude)
+}
+
+fn burning_ship_scalar(c_x: f32, c_y: f32, max_iter: u32, bailout: f32) -> (u32, f32) {
+    let mut x = 0.0f32;
+    let mut y = 0.0f32;
+    let mut count     = 0;
+    let mut magnitude = 0.0;
+    for _ in 0..max_iter {
+        let xx = x * x;
+        let yy = y * y;
+        if !(xx + yy < bailout) {
+            magnitude = xx + yy;
+            break;
+        }
+        count += 1;
+        let xy = x.abs() * y.abs();
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude)
+}
+
+// f64 counterparts of the scalar kernels for views too deep for f32 pixel
+// coordinates; see DEEP_ZOOM_ULPS.
+fn mandelbrot_f64(z_x: f64, z_y: f64, c_x: f64, c_y: f64, max_iter: u32, bailout: f32) -> (u32, f32) {
+    let mut x = z_x;
+    let mut y = z_y;
+    let mut count     = 0;
+    let mut magnitude = 0.0;
+    for _ in 0..max_iter {
+        let xy = x * y;
+        let xx = x * x;
+        let yy = y * y;
+        if !(xx + yy < bailout as f64) {
+            magnitude = xx + yy;
+            break;
+        }
+        count += 1;
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude as f32)
+}
+
+fn burning_ship_f64(c_x: f64, c_y: f64, max_iter: u32, bailout: f32) -> (u32, f32) {
+    let mut x = 0.0f64;
+    let mut y = 0.0f64;
+    let mut count     = 0;
+    let mut magnitude = 0.0;
+    for _ in 0..max_iter {
+        let xx = x * x;
+        let yy = y * y;
+        if !(xx + yy < bailout as f64) {
+            magnitude = xx + yy;
+            break;
+        }
+        count += 1;
+        let xy = x.abs() * y.abs();
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude as f32)
+}
+
+// An unevaluated sum hi + lo with |lo| <= ulp(hi) / 2, good for about 106
+// bits of mantissa. Only the reference orbit and glitched pixels pay for it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+impl DoubleDouble {
+    fn new(value: f64) -> DoubleDouble {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    fn normalize(hi: f64, lo: f64) -> DoubleDouble {
+        let sum = hi + lo;
+        DoubleDouble { hi: sum, lo: lo - (sum - hi) }
+    }
+
+    fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        // Knuth's two-sum recovers the rounding error of hi + hi exactly.
+        let sum   = self.hi + other.hi;
+        let bits  = sum - self.hi;
+        let error = (self.hi - (sum - bits)) + (other.hi - bits);
+        DoubleDouble::normalize(sum, error + self.lo + other.lo)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + DoubleDouble { hi: -other.hi, lo: -other.lo }
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        // The fused multiply-add yields the exact error of hi * hi.
+        let product = self.hi * other.hi;
+        let error   = self.hi.mul_add(other.hi, -product);
+        DoubleDouble::normalize(product, error + self.hi * other.lo + self.lo * other.hi)
+    }
+}
+
+// A signed fixed-point number of `bits` fraction bits, for reference orbits
+// that need more than DoubleDouble's 106. Costs grow with the bits, which is
+// fine for the single orbit per frame that uses it.
+#[derive(Clone, PartialEq, Debug)]
+struct FixedPoint {
+    mantissa: BigInt,
+    bits:     usize,
+}
+
+impl FixedPoint {
+    fn new(value: f64, bits: usize) -> FixedPoint {
+        let (mantissa, exponent, sign) = Float::integer_decode(value);
+        let mantissa = BigInt::from_u64(mantissa).unwrap_or_default() * BigInt::from_i8(sign).unwrap_or_default();
+        let shift    = exponent as isize + bits as isize;
+        FixedPoint {
+            mantissa: if shift >= 0 { mantissa << shift as usize } else { mantissa >> (-shift) as usize },
+            bits,
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        // Keep the top 64 bits so the conversion never overflows, then scale
+        // in two steps so neither factor underflows.
+        let shift = self.mantissa.bits().saturating_sub(64);
+        let value = (&self.mantissa >> shift).to_f64().unwrap_or(0.0);
+        let scale = shift as i32 - self.bits as i32;
+        value * 2.0f64.powi(scale / 2) * 2.0f64.powi(scale - scale / 2)
+    }
+
+    // `digits` times ten to the `scale`, rounded to nearest.
+    fn from_decimal(digits: &BigInt, scale: i32, bits: usize) -> FixedPoint {
+        let ten      = BigInt::from_u8(10).unwrap_or_default();
+        let mantissa = if scale >= 0 {
+            (digits * num::pow(ten, scale as usize)) << bits
+        } else {
+            let divisor = num::pow(ten, (-scale) as usize);
+            let half    = &divisor / BigInt::from_u8(2).unwrap_or_default();
+            let rounded = ((digits.abs() << bits) + half) / divisor;
+            if digits.is_negative() { -rounded } else { rounded }
+        };
+        FixedPoint { mantissa, bits }
+    }
+
+    // Rounded to the decimal places the bits resolve, without trailing zeros,
+    // so a decimal with that many places comes back as written.
+    fn to_decimal(&self) -> String {
+        let places = (self.bits as f64 * f64::consts::LOG10_2).floor() as usize;
+        let half   = BigInt::from_u8(1).unwrap_or_default() << self.bits.saturating_sub(1);
+        let scaled = (self.mantissa.abs() * num::pow(BigInt::from_u8(10).unwrap_or_default(), places) + half)
+            >> self.bits;
+        let text   = format!("{:0>width$}", scaled.to_string(), width = places + 1);
+        let (whole, fraction) = text.split_at(text.len() - places);
+        let fraction = fraction.trim_end_matches('0');
+        format!("{}{}{}{}", if self.mantissa.is_negative() { "-" } else { "" }, whole,
+                if fraction.is_empty() { "" } else { "." }, fraction)
+    }
+}
+
+// Decimal places past which parse_decimal gives up; no reference orbit
+// resolves them.
+const MAX_DECIMAL_SCALE: i32 = 4 * MAX_REFERENCE_BITS as i32;
+
+// An optionally signed decimal with at most one point and an optional
+// exponent, as its digits and the power of ten they are scaled by.
+fn parse_decimal(text: &str) -> Option<(BigInt, i32)> {
+    let text             = text.trim();
+    let (number, power)  = match text.find(['e', 'E']) {
+        Some(at) => (&text[..at], text[at + 1..].parse::<i32>().ok()?),
+        None     => (text, 0),
+    };
+    let (negative, number) = match number.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None       => (false, number.strip_prefix('+').unwrap_or(number)),
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let digits: BigInt = format!("{}{}", whole, fraction).parse().ok()?;
+    let scale          = power.checked_sub(fraction.len() as i32)?;
+    if scale.abs() > MAX_DECIMAL_SCALE {
+        return None;
+    }
+    Some((if negative { -digits } else { digits }, scale))
+}
+
+// Fraction bits a parsed PrecisePoint keeps beyond its last decimal place,
+// for the reference orbit to lose.
+const PRECISE_GUARD_BITS: u32 = 64;
+
+/// A point of the plane in fixed point, for view centers deeper than f64
+/// can place. It reads and writes decimal strings, so saved deep views keep
+/// every digit.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrecisePoint {
+    re: FixedPoint,
+    im: FixedPoint,
+}
+
+impl PrecisePoint {
+    pub fn new(point: Complex<f64>, bits: u32) -> PrecisePoint {
+        assert!(bits > 0 && bits <= MAX_REFERENCE_BITS, "precise points take 1 to {} bits", MAX_REFERENCE_BITS);
+        PrecisePoint { re: FixedPoint::new(point.re, bits as usize), im: FixedPoint::new(point.im, bits as usize) }
+    }
+
+    /// Parses decimal coordinates, with enough fraction bits for their last
+    /// digit plus PRECISE_GUARD_BITS, up to MAX_REFERENCE_BITS.
+    pub fn parse(re: &str, im: &str) -> Result<PrecisePoint, String> {
+        let decimal  = |text: &str| parse_decimal(text).ok_or_else(|| format!("bad decimal coordinate {:?}", text));
+        let (re, im) = (decimal(re)?, decimal(im)?);
+        let places   = cmp::max(0, -cmp::min(re.1, im.1));
+        let bits     = (places as f64 * f64::consts::LOG2_10).ceil() as u32 + PRECISE_GUARD_BITS;
+        let bits     = cmp::min(bits, MAX_REFERENCE_BITS) as usize;
+        Ok(PrecisePoint {
+            re: FixedPoint::from_decimal(&re.0, re.1, bits),
+            im: FixedPoint::from_decimal(&im.0, im.1, bits),
+        })
+    }
+
+    /// Both coordinates as decimals, rounded to the places the bits resolve.
+    pub fn to_strings(&self) -> (String, String) {
+        (self.re.to_decimal(), self.im.to_decimal())
+    }
+
+    pub fn to_f64(&self) -> Complex<f64> {
+        Complex { re: self.re.to_f64(), im: self.im.to_f64() }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.re.bits as u32
+    }
+
+    /// This point moved by `delta`, at the same precision; for panning and
+    /// zooming around a deep center.
+    pub fn offset(&self, delta: Complex<f64>) -> PrecisePoint {
+        PrecisePoint {
+            re: self.re.clone() + FixedPoint::new(delta.re, self.re.bits),
+            im: self.im.clone() + FixedPoint::new(delta.im, self.im.bits),
+        }
+    }
+}
+
+impl Add for FixedPoint {
+    type Output = FixedPoint;
+
+    fn add(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint { mantissa: self.mantissa + other.mantissa, bits: self.bits }
+    }
+}
+
+impl Sub for FixedPoint {
+    type Output = FixedPoint;
+
+    fn sub(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint { mantissa: self.mantissa - other.mantissa, bits: self.bits }
+    }
+}
+
+impl Mul for FixedPoint {
+    type Output = FixedPoint;
+
+    fn mul(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint { mantissa: (self.mantissa * other.mantissa) >> self.bits, bits: self.bits }
+    }
+}
+
+// What the reference orbit needs of the type it is computed in.
+trait OrbitFloat: Clone + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> {
+    fn round(&self) -> f64;
+}
+
+impl OrbitFloat for DoubleDouble {
+    fn round(&self) -> f64 {
+        self.to_f64()
+    }
+}
+
+impl OrbitFloat for FixedPoint {
+    fn round(&self) -> f64 {
+        self.to_f64()
+    }
+}
+
+// The orbit of z under z^2 + c, rounded to f64 per step, up to and including
+// the first point past the escape radius.
+fn reference_orbit<T: OrbitFloat>(z: (T, T), c: (T, T), max_iter: u32, bailout: f32) -> Vec<Complex<f64>> {
+    let (mut x, mut y) = z;
+    let mut orbit      = Vec::with_capacity(max_iter as usize + 1);
+    orbit.push(Complex { re: x.round(), im: y.round() });
+    for _ in 0..max_iter {
+        let xy = x.clone() * y.clone();
+        let xx = x.clone() * x;
+        let yy = y.clone() * y;
+        if !((xx.clone() + yy.clone()).round() < bailout as f64) { break }
+        x = xx - yy + c.0.clone();
+        y = xy.clone() + xy + c.1.clone();
+        orbit.push(Complex { re: x.round(), im: y.round() });
+    }
+    orbit
+}
+
+// Full-precision iteration for pixels the perturbation path flags as glitched.
+fn mandelbrot_exact<T: OrbitFloat>(z: (T, T), c: (T, T), max_iter: u32, bailout: f32) -> (u32, f32) {
+    let (mut x, mut y) = z;
+    let mut count      = 0;
+    let mut magnitude  = 0.0;
+    for _ in 0..max_iter {
+        let xy = x.clone() * y.clone();
+        let xx = x.clone() * x;
+        let yy = y.clone() * y;
+        let m  = (xx.clone() + yy.clone()).round();
+        if !(m < bailout as f64) {
+            magnitude = m;
+            break;
+        }
+        count += 1;
+        x = xx - yy + c.0.clone();
+        y = xy.clone() + xy + c.1.clone();
+    }
+    (count, magnitude as f32)
+}
+
+// Orbit of the view center, computed in double-double or, with
+// `reference_bits` set or a precise center given, in fixed point and rounded
+// to f64 per step. Every other pixel of a deep view only iterates its small
+// offset from this orbit, which f64 holds at full relative precision.
+struct ReferenceOrbit {
+    point:   Complex<f64>,
+    // The precise center, which glitched pixels are then iterated around in
+    // fixed point; a DoubleDouble of `point` no longer places them.
+    exact:   Option<PrecisePoint>,
+    // The Julia constant, or None when c is the pixel itself.
+    julia:   Option<Complex<f64>>,
+    orbit:   Vec<Complex<f64>>,
+    // The render's squared escape radius, which pixels and the orbit share.
+    bailout: f32,
+}
+
+// Pauldelbrot's criterion: once |Z + dz|^2 falls below this fraction of
+// |Z|^2 the offset has lost the precision the reference carried and the
+// pixel is iterated again on its own.
+const GLITCH_TOLERANCE: f64 = 1e-6;
+
+impl ReferenceOrbit {
+    fn new(point: Complex<f64>, fractal: FractalKind, max_iter: u32, bits: u32, bailout: f32)
+           -> Option<ReferenceOrbit> {
+        if bits != 0 {
+            return ReferenceOrbit::precise(&PrecisePoint::new(point, bits), fractal, max_iter, bailout)
+                .map(|reference| ReferenceOrbit { exact: None, ..reference });
+        }
+        let julia = match fractal {
+            FractalKind::Mandelbrot  => None,
+            FractalKind::Julia { c } => Some(c),
+            FractalKind::BurningShip => return None,
+        };
+        let z     = (DoubleDouble::new(point.re), DoubleDouble::new(point.im));
+        let c     = julia.map(|c| (DoubleDouble::new(c.re), DoubleDouble::new(c.im))).unwrap_or(z);
+        let orbit = reference_orbit(z, c, max_iter, bailout);
+        Some(ReferenceOrbit { point, exact: None, julia, orbit, bailout })
+    }
+
+    // Orbit of `center` at its own precision.
+    fn precise(center: &PrecisePoint, fractal: FractalKind, max_iter: u32, bailout: f32) -> Option<ReferenceOrbit> {
+        let julia = match fractal {
+            FractalKind::Mandelbrot  => None,
+            FractalKind::Julia { c } => Some(c),
+            FractalKind::BurningShip => return None,
+        };
+        let orbit = reference_orbit((center.re.clone(), center.im.clone()), julia_fixed(julia, center), max_iter,
+                                    bailout);
+        Some(ReferenceOrbit { point: center.to_f64(), exact: Some(center.clone()), julia, orbit, bailout })
+    }
+
+    // Iterations of the reference point itself before it escaped, or the
+    // limit when it never did.
+    fn iterations(&self) -> u32 {
+        self.orbit.len() as u32 - 1
+    }
+
+    // Iterates the pixel at `offset` from the reference point. None when the
+    // pixel glitched or outlived the reference and needs iterate_exact.
+    fn iterate(&self, offset: Complex<f64>, max_iter: u32) -> Option<(u32, f32)> {
+        let (mut dx, mut dy) = (offset.re, offset.im);
+        let (dc_x, dc_y)     = if self.julia.is_some() { (0.0, 0.0) } else { (offset.re, offset.im) };
+        let mut count        = 0;
+        for n in 0..max_iter as usize {
+            let reference = match self.orbit.get(n) {
+                Some(&z) => z,
+                None     => return None,
+            };
+            let (x, y) = (reference.re + dx, reference.im + dy);
+            let m      = x * x + y * y;
+            if !(m < self.bailout as f64) {
+                return Some((count, m as f32));
+            }
+            if m < GLITCH_TOLERANCE * reference.norm_sqr() {
+                return None;
+            }
+            count += 1;
+            // dz' = 2 Z dz + dz^2 + dc
+            let (zx, zy) = (reference.re, reference.im);
+            let next_x   = 2.0 * (zx * dx - zy * dy) + (dx * dx - dy * dy) + dc_x;
+            let next_y   = 2.0 * (zx * dy + zy * dx) + 2.0 * dx * dy + dc_y;
+            dx = next_x;
+            dy = next_y;
+        }
+        Some((count, 0.0))
+    }
+
+    fn iterate_exact(&self, offset: Complex<f64>, max_iter: u32) -> (u32, f32) {
+        if let Some(ref exact) = self.exact {
+            let pixel = exact.offset(offset);
+            let c     = julia_fixed(self.julia, &pixel);
+            return mandelbrot_exact((pixel.re, pixel.im), c, max_iter, self.bailout);
+        }
+        let z = (DoubleDouble::new(self.point.re) + DoubleDouble::new(offset.re),
+                 DoubleDouble::new(self.point.im) + DoubleDouble::new(offset.im));
+        let c = self.julia.map(|c| (DoubleDouble::new(c.re), DoubleDouble::new(c.im))).unwrap_or(z);
+        mandelbrot_exact(z, c, max_iter, self.bailout)
+    }
+}
+
+// c for iterating `point` in fixed point: the Julia constant at the point's
+// precision, or the point itself.
+fn julia_fixed(julia: Option<Complex<f64>>, point: &PrecisePoint) -> (FixedPoint, FixedPoint) {
+    match julia {
+        Some(c) => (FixedPoint::new(c.re, point.re.bits), FixedPoint::new(c.im, point.re.bits)),
+        None    => (point.re.clone(), point.im.clone()),
+    }
+}
+
+// The 8-wide kernels are built for AVX2 whatever the target baseline is, so
+// only call them once has_avx2() said yes.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx,avx2")]
+#[inline(never)]
+unsafe fn mandelbrot_vector8(z_x: f32x8, z_y: f32x8, c_x: f32x8, c_y: f32x8, max_iter: u32, bailout: f32)
+                      -> (u32x8, f32x8) {
+    let (q_x, q_y) = (c_x - f32x8::splat(0.25), c_y * c_y);
+    let q          = q_x * q_x + q_y;
+    let bulb       = c_x + f32x8::splat(1.0);
+    let mut inside = ((q * (q + q_x)).simd_lt(q_y * f32x8::splat(0.25))
+                      | (bulb * bulb + q_y).simd_lt(f32x8::splat(0.0625)))
+                     & z_x.simd_eq(c_x) & z_y.simd_eq(c_y);
+    let mut x = z_x;
+    let mut y = z_y;
+    let mut count     = u32x8::splat(0);
+    let mut magnitude = f32x8::splat(0.0);
+    let tolerance     = f32x8::splat(PERIODICITY_TOLERANCE);
+    let (mut snapshot_x, mut snapshot_y, mut snapshot) = (x, y, 1);
+    for i in 0..max_iter as usize {
+        let xy = x * y;
+        let xx = x * x;
+        let yy = y * y;
+        let sum = xx + yy;
+        let mask = sum.simd_lt(f32x8::splat(bailout)) & !inside;
+        magnitude = (mask | inside | magnitude.simd_gt(f32x8::splat(0.0))).select(magnitude, sum);
+        if !mask.any() { break }
+        count += mask.select(u32x8::splat(1), u32x8::splat(0));
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+        inside |= mask & abs_x8(x - snapshot_x).simd_lt(tolerance) & abs_x8(y - snapshot_y).simd_lt(tolerance);
+        if i + 1 == snapshot {
+            snapshot_x = x;
+            snapshot_y = y;
+            snapshot  *= 2;
+        }
+    }
+    (inside.select(u32x8::splat(max_iter), count), magnitude)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx,avx2")]
+#[inline]
+unsafe fn abs_x8(v: f32x8) -> f32x8 {
+    mem::transmute(mem::transmute::<f32x8, u32x8>(v) & u32x8::splat(0x7FFF_FFFF))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx,avx2")]
+#[inline(never)]
+unsafe fn burning_ship_vector8(c_x: f32x8, c_y: f32x8, max_iter: u32, bailout: f32) -> (u32x8, f32x8) {
+    let mut x = f32x8::splat(0.0);
+    let mut y = f32x8::splat(0.0);
+    let mut count     = u32x8::splat(0);
+    let mut magnitude = f32x8::splat(0.0);
+    for _ in 0..max_iter as usize {
+        let xx = x * x;
+        let yy = y * y;
+        let sum = xx + yy;
+        let mask = sum.simd_lt(f32x8::splat(bailout));
+        magnitude = (mask | magnitude.simd_gt(f32x8::splat(0.0))).select(magnitude, sum);
+        if !mask.any() { break }
+        count += mask.select(u32x8::splat(1), u32x8::splat(0));
+        let xy = abs_x8(x) * abs_x8(y);
+        x = xx - yy + c_x;
+        y = xy + xy + c_y;
+    }
+    (count, magnitude)
+}
+
+// Normalized iteration count n + 1 - log2(log|z|), split into the whole count
+// and the fraction a cache cell stores. Interior points keep their integer
+// count, so the log never sees a magnitude that did not escape.
+#[inline]
+fn smooth_count(count: u32, magnitude: f32, max_iter: u32) -> (u32, f32) {
+    if count >= max_iter {
+        return (count, 0.0);
+    }
+    // f32::max drops a NaN, so even a non-finite magnitude lands on zero.
+    let smooth = (count as f32 + 1.0 - (0.5 * magnitude.ln()).log2()).max(0.0);
+    (smooth as u32, smooth.fract())
+}
+
+// Splits a palette position into the two neighbouring stops and the blend
+// factor between them, wrapping negative and out-of-range positions.
+#[inline]
+fn palette_index(val: f32, len: usize) -> (usize, usize, f32) {
+    let index = val.floor();
+    let left  = index.rem_euclid(len as f32) as usize % len;
+    let right = (left + 1) % len;
+    (left, right, val - index)
+}
+
+#[inline]
+pub fn color(iteration: f32, palette: &[(f32, f32, f32)]) -> u32 {
+    let (r, g, b) = interpolate(iteration, palette);
+    pack_rgb(r, g, b)
+}
+
+#[inline]
+fn interpolate(iteration: f32, palette: &[(f32, f32, f32)]) -> (f32, f32, f32) {
+    let val = (iteration % 12.0) * (palette.len() as f32) / 12.0;
+    let (left, right, p) = palette_index(val, palette.len());
+    let (r1, g1, b1) = palette[left];
+    let (r2, g2, b2) = palette[right];
+    (r1 + (r2 - r1) * p, g1 + (g2 - g1) * p, b1 + (b2 - b1) * p)
+}
+
+// Encodes a 0 ..= 255 channel for a display with the given gamma, which
+// brightens the mid-tones the plain interpolation leaves dark.
+#[inline]
+pub fn apply_gamma(channel: f32, gamma: f32) -> f32 {
+    255.0 * (channel.max(0.0) / 255.0).powf(1.0 / gamma)
+}
+
+// Clamps each channel to 0 ..= 255 before packing, so a palette stop outside
+// that range can never carry into the neighbouring channel or the top byte.
+#[inline]
+fn pack_rgb(r: f32, g: f32, b: f32) -> u32 {
+    let channel = |v: f32| v.max(0.0).min(255.0) as u32;
+    (channel(r) << 16) | (channel(g) << 8) | channel(b)
+}
+
+// Entries per iteration step; the fractional escape is quantized to this.
+const LUT_STEPS: usize = 64;
+
+// `color` sampled across its 12-iteration period and gamma encoded, so coloring
+// a pixel is one multiply and one indexed load instead of a modulo, two
+// lookups, three lerps and three powers.
+pub struct PaletteLut {
+    colors: Vec<u32>,
+}
+
+impl PaletteLut {
+    pub fn new(palette: &[(f32, f32, f32)], gamma: f32) -> PaletteLut {
+        debug_assert!(palette.iter().all(|&(r, g, b)| [r, g, b].iter().all(|&c| (0.0..=255.0).contains(&c))),
+                      "palette stops must be in 0 ..= 255");
+        let colors = (0 .. 12 * LUT_STEPS).map(|i| {
+            let (r, g, b) = interpolate(i as f32 / LUT_STEPS as f32, palette);
+            pack_rgb(apply_gamma(r, gamma), apply_gamma(g, gamma), apply_gamma(b, gamma))
+        }).collect();
+        PaletteLut { colors }
+    }
+
+    #[inline]
+    pub fn color(&self, iteration: f32) -> u32 {
+        let index = (iteration.max(0.0) * LUT_STEPS as f32) as usize;
+        self.colors[index % self.colors.len()]
+    }
+}
+
+// One pixel of the iteration cache: the escape count plus the fractional part
+// of the escape used by continuous coloring modes. `pack` turns a non-finite
+// fraction into the error marker, so every coloring mode can flag it.
+trait CacheCell: Copy + Default + Send {
+    fn pack(count: u32, fraction: f32) -> Self;
+    fn count(self) -> u32;
+    fn fraction(self) -> f32;
+    fn is_error(self) -> bool;
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct FullCell {
+    count:    u32,
+    fraction: f32,
+}
+
+impl CacheCell for FullCell {
+    fn pack(count: u32, fraction: f32) -> FullCell {
+        let fraction = if fraction.is_finite() { fraction } else { f32::NAN };
+        FullCell { count, fraction }
+    }
+
+    fn count(self) -> u32 { self.count }
+
+    fn fraction(self) -> f32 { self.fraction }
+
+    fn is_error(self) -> bool { self.fraction.is_nan() }
+}
+
+// Little-endian u16 count followed by the fraction quantized to 0 ..= 254;
+// a fraction byte of 255 marks an error pixel.
+#[derive(Clone, Copy, Default)]
+pub struct CompactCell([u8; 3]);
+
+const COMPACT_ERROR: u8 = 0xFF;
+
+impl CacheCell for CompactCell {
+    fn pack(count: u32, fraction: f32) -> CompactCell {
+        let count    = cmp::min(count, u16::MAX as u32);
+        let fraction = if fraction.is_finite() {
+            (fraction.max(0.0).min(1.0) * 254.0).round() as u8
+        } else {
+            COMPACT_ERROR
+        };
+        CompactCell([count as u8, (count >> 8) as u8, fraction])
+    }
+
+    fn count(self) -> u32 { self.0[0] as u32 | (self.0[1] as u32) << 8 }
+
+    fn fraction(self) -> f32 { self.0[2] as f32 / 254.0 }
+
+    fn is_error(self) -> bool { self.0[2] == COMPACT_ERROR }
+}
+
+// Caches at least this large use CompactCell, as long as the iteration limit
+// fits its u16 count.
+const COMPACT_CACHE_PIXELS: usize = 4096 * 4096;
+
+pub enum IterationCache {
+    Full(Vec<FullCell>),
+    Compact(Vec<CompactCell>),
+}
+
+impl IterationCache {
+    fn new(len: usize, limit: u32) -> IterationCache {
+        if len >= COMPACT_CACHE_PIXELS && limit <= u16::MAX as u32 {
+            IterationCache::Compact(vec![CompactCell::default(); len])
+        } else {
+            IterationCache::Full(vec![FullCell::default(); len])
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            IterationCache::Full(_)    => "full",
+            IterationCache::Compact(_) => "compact",
+        }
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        match *self {
+            IterationCache::Full(ref cells)    => cells.len() * mem::size_of::<FullCell>(),
+            IterationCache::Compact(ref cells) => cells.len() * mem::size_of::<CompactCell>(),
+        }
+    }
+
+    fn render(&mut self,
+              bounds:      (usize, usize),
+              upper_left:  Complex<f64>,
+              lower_right: Complex<f64>,
+              settings:    &RenderSettings,
+              jitter:      (f32, f32),
+              cancel:      &CancelToken) -> (Vec<TileTiming>, Option<u32>) {
+        match *self {
+            IterationCache::Full(ref mut cells) =>
+                render_parallel(cells, bounds, upper_left, lower_right, settings, jitter, cancel),
+            IterationCache::Compact(ref mut cells) =>
+                render_parallel(cells, bounds, upper_left, lower_right, settings, jitter, cancel),
+        }
+    }
+
+    pub fn colorize(&self, pixels: &mut [u32], palette: &PaletteLut, error_color: u32) -> usize {
+        match *self {
+            IterationCache::Full(ref cells)    => colorize(cells, pixels, palette, error_color),
+            IterationCache::Compact(ref cells) => colorize(cells, pixels, palette, error_color),
+        }
+    }
+
+    pub fn colorize_raw(&self, pixels: &mut [u32], limit: u32, error_color: u32) -> usize {
+        match *self {
+            IterationCache::Full(ref cells)    => colorize_raw(cells, pixels, limit, error_color),
+            IterationCache::Compact(ref cells) => colorize_raw(cells, pixels, limit, error_color),
+        }
+    }
+
+    pub fn colorize_histogram(&self, pixels: &mut [u32], palette: &PaletteLut, limit: u32, error_color: u32) -> usize {
+        match *self {
+            IterationCache::Full(ref cells)    => colorize_histogram(cells, pixels, palette, limit, error_color),
+            IterationCache::Compact(ref cells) => colorize_histogram(cells, pixels, palette, limit, error_color),
+        }
+    }
+
+    pub fn colorize_trap(&self, pixels: &mut [u32], palette: &PaletteLut, error_color: u32) -> usize {
+        match *self {
+            IterationCache::Full(ref cells)    => colorize_trap(cells, pixels, palette, error_color),
+            IterationCache::Compact(ref cells) => colorize_trap(cells, pixels, palette, error_color),
+        }
+    }
+
+    pub fn colorize_distance(&self, pixels: &mut [u32], limit: u32, error_color: u32) -> usize {
+        match *self {
+            IterationCache::Full(ref cells)    => colorize_distance(cells, pixels, limit, error_color),
+            IterationCache::Compact(ref cells) => colorize_distance(cells, pixels, limit, error_color),
+        }
+    }
+
+    pub fn count(&self, index: usize) -> Option<u32> {
+        match *self {
+            IterationCache::Full(ref cells)    => cells.get(index).map(|c| c.count()),
+            IterationCache::Compact(ref cells) => cells.get(index).map(|c| c.count()),
+        }
+    }
+
+    // Smooth iteration count per pixel, the field the contour export traces.
+    pub fn values(&self) -> Vec<f32> {
+        match *self {
+            IterationCache::Full(ref cells)    => cells.iter().map(|c| c.count() as f32 + c.fraction()).collect(),
+            IterationCache::Compact(ref cells) => cells.iter().map(|c| c.count() as f32 + c.fraction()).collect(),
+        }
+    }
+}
+
+// Bilinear resampling of `cells`, a `bounds` grid, into `len` cells, the
+// i-th taken from the fractional cell position `source(i)`. Escape fractions
+// continue the count, so their smooth counts are blended; trap and distance
+// values are blended on their own, keeping the nearest cell's count. Next to
+// an error cell the nearest cell is copied as is.
+fn resample<C: CacheCell, F: Fn(usize) -> (f64, f64)>(cells:  &[C],
+                                                      bounds: (usize, usize),
+                                                      len:    usize,
+                                                      value:  CellValue,
+                                                      source: F) -> Vec<FullCell> {
+    let cell = |x: usize, y: usize| cells[cmp::min(y, bounds.1 - 1) * bounds.0 + cmp::min(x, bounds.0 - 1)];
+    (0 .. len).map(|i| {
+        let (x, y)   = source(i);
+        let (fx, fy) = ((x - x.floor()) as f32, (y - y.floor()) as f32);
+        let (x, y)   = (x as usize, y as usize);
+        let corners  = [cell(x, y), cell(x + 1, y), cell(x, y + 1), cell(x + 1, y + 1)];
+        let nearest  = corners[(fx >= 0.5) as usize + 2 * (fy >= 0.5) as usize];
+        if corners.iter().any(|c| c.is_error()) {
+            return FullCell::pack(nearest.count(), if nearest.is_error() { f32::NAN } else { nearest.fraction() });
+        }
+        let blend = |v: [f32; 4]| {
+            let top    = v[0] + (v[1] - v[0]) * fx;
+            let bottom = v[2] + (v[3] - v[2]) * fx;
+            top + (bottom - top) * fy
+        };
+        if value == CellValue::Escape {
+            let smooth = blend(corners.map(|c| c.count() as f32 + c.fraction()));
+            FullCell::pack(smooth.floor() as u32, smooth - smooth.floor())
+        } else {
+            FullCell::pack(nearest.count(), blend(corners.map(|c| c.fraction())))
+        }
+    }).collect()
+}
+
+// Both colorizers paint error cells with `error_color` and return how many
+// there were.
+fn colorize<C: CacheCell>(cells: &[C], pixels: &mut [u32], palette: &PaletteLut, error_color: u32) -> usize {
+    let mut errors = 0;
+    for (pixel, cell) in pixels.iter_mut().zip(cells.iter()) {
+        if cell.is_error() {
+            *pixel  = error_color;
+            errors += 1;
+        } else {
+            *pixel = palette.color(cell.count() as f32 + cell.fraction());
+        }
+    }
+    errors
+}
+
+// Palette colors like colorize, but interpolated, gamma encoded and decoded
+// to linear light in f32 instead of going through the 8-bit lookup table.
+fn linear_samples<C: CacheCell>(cells:   &[C],
+                                palette: &[(f32, f32, f32)],
+                                gamma:   f32,
+                                error:   [f32; 4]) -> Vec<[f32; 4]> {
+    let linear = |channel: f32| decode_srgb((apply_gamma(channel, gamma) / 255.0).min(1.0));
+    cells.iter().map(|cell| {
+        if cell.is_error() {
+            return error;
+        }
+        let value     = cell.count() as f32 + cell.fraction();
+        let (r, g, b) = interpolate(value, palette);
+        [linear(r), linear(g), linear(b), value]
+    }).collect()
+}
+
+fn colorize_raw<C: CacheCell>(cells: &[C], pixels: &mut [u32], limit: u32, error_color: u32) -> usize {
+    let limit      = cmp::max(limit, 1);
+    let mut errors = 0;
+    for (pixel, cell) in pixels.iter_mut().zip(cells.iter()) {
+        if cell.is_error() {
+            *pixel  = error_color;
+            errors += 1;
+        } else {
+            *pixel = cmp::min(cell.count() * 255 / limit, 255) * 0x0001_0101;
+        }
+    }
+    errors
+}
+
+// Histogram equalization: an escaped cell lands on the palette at its rank
+// among all escaped cells, with the smooth fraction interpolating inside its
+// count's bucket. The ranks cover one palette cycle; interior cells keep the
+// color Palette mode gives them.
+fn colorize_histogram<C: CacheCell>(cells:       &[C],
+                                    pixels:      &mut [u32],
+                                    palette:     &PaletteLut,
+                                    limit:       u32,
+                                    error_color: u32) -> usize {
+    let mut histogram = vec![0usize; limit as usize];
+    for cell in cells {
+        if !cell.is_error() && cell.count() < limit {
+            histogram[cell.count() as usize] += 1;
+        }
+    }
+    // below[n] counts the escaped cells with a count under n.
+    let mut below   = Vec::with_capacity(histogram.len());
+    let mut escaped = 0;
+    for &bucket in &histogram {
+        below.push(escaped);
+        escaped += bucket;
+    }
+    let escaped    = cmp::max(escaped, 1) as f32;
+    let span       = 12.0 - 1.0 / LUT_STEPS as f32;
+    let interior   = palette.color(limit as f32);
+    let mut errors = 0;
+    for (pixel, cell) in pixels.iter_mut().zip(cells.iter()) {
+        if cell.is_error() {
+            *pixel  = error_color;
+            errors += 1;
+        } else if cell.count() >= limit {
+            *pixel = interior;
+        } else {
+            let count = cell.count() as usize;
+            let rank  = (below[count] as f32 + cell.fraction() * histogram[count] as f32) / escaped;
+            *pixel = palette.color(rank * span);
+        }
+    }
+    errors
+}
+
+// Sweeps one palette cycle from the trap outwards. The square root spreads
+// the close approaches, where the detail is, over more of it.
+fn colorize_trap<C: CacheCell>(cells: &[C], pixels: &mut [u32], palette: &PaletteLut, error_color: u32) -> usize {
+    let span       = 12.0 - 1.0 / LUT_STEPS as f32;
+    let mut errors = 0;
+    for (pixel, cell) in pixels.iter_mut().zip(cells.iter()) {
+        if cell.is_error() {
+            *pixel  = error_color;
+            errors += 1;
+        } else {
+            *pixel = palette.color(cell.fraction().sqrt() * span);
+        }
+    }
+    errors
+}
+
+// Exterior cells within a pixel of the set fade from white at the boundary to
+// black; the interior stays black.
+fn colorize_distance<C: CacheCell>(cells: &[C], pixels: &mut [u32], limit: u32, error_color: u32) -> usize {
+    let mut errors = 0;
+    for (pixel, cell) in pixels.iter_mut().zip(cells.iter()) {
+        if cell.is_error() {
+            *pixel  = error_color;
+            errors += 1;
+        } else if cell.count() >= limit {
+            *pixel = 0;
+        } else {
+            *pixel = ((1.0 - cell.fraction()) * 255.0) as u32 * 0x0001_0101;
+        }
+    }
+    errors
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DownsampleFilter {
+    Box,
+    Triangle,
+    Lanczos,
+}
+
+impl DownsampleFilter {
+    // Support radius, in output pixels.
+    fn radius(self) -> f32 {
+        match self {
+            DownsampleFilter::Box      => 0.5,
+            DownsampleFilter::Triangle => 1.0,
+            DownsampleFilter::Lanczos  => 3.0,
+        }
+    }
+
+    // Weight of a sample `x` output pixels away from the output pixel center.
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            DownsampleFilter::Box      => if x < 0.5 { 1.0 } else { 0.0 },
+            DownsampleFilter::Triangle => (1.0 - x).max(0.0),
+            DownsampleFilter::Lanczos  => {
+                if x < 1e-6 { return 1.0 }
+                if x >= 3.0 { return 0.0 }
+                let px = f32::consts::PI * x;
+                3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+            },
+        }
+    }
+
+    // Normalized taps for every output position along one axis.
+    fn taps(self, len: usize, factor: usize) -> Vec<Vec<(usize, f32)>> {
+        let src_len = (len * factor) as isize;
+        let radius  = self.radius() * factor as f32;
+        (0 .. len).map(|i| {
+            let center = (i as f32 + 0.5) * factor as f32;
+            let first  = (center - radius).floor() as isize;
+            let last   = (center + radius).ceil()  as isize;
+            let mut taps: Vec<(usize, f32)> = (first .. last).filter_map(|s| {
+                let weight = self.weight((s as f32 + 0.5 - center) / factor as f32);
+                let index  = cmp::min(cmp::max(s, 0), src_len - 1) as usize;
+                if weight != 0.0 { Some((index, weight)) } else { None }
+            }).collect();
+            let total: f32 = taps.iter().map(|&(_, w)| w).sum();
+            for tap in taps.iter_mut() {
+                tap.1 /= total;
+            }
+            taps
+        }).collect()
+    }
+}
+
+// sRGB transfer function, between an 8-bit encoded channel and linear light
+// in 0 ..= 1.
+pub fn srgb_to_linear(c: u32) -> f32 {
+    decode_srgb(c as f32 / 255.0)
+}
+
+// The sRGB transfer function for an encoded value in 0.0 ..= 1.0.
+fn decode_srgb(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(v: f32) -> u32 {
+    let v = v.max(0.0).min(1.0);
+    let c = if v <= 0.003_130_8 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round() as u32
+}
+
+// Reduces a (width * factor) x (height * factor) buffer to width x height with
+// a separable `filter`.
+pub fn downsample(src: &[u32], width: usize, height: usize, factor: usize, filter: DownsampleFilter) -> Vec<u32> {
+    // Filter in linear light; averaging the encoded values darkens every
+    // edge between a bright and a dark band.
+    let unpack = |p: u32| [srgb_to_linear((p >> 16) & 0xFF), srgb_to_linear((p >> 8) & 0xFF), srgb_to_linear(p & 0xFF)];
+    let linear: Vec<[f32; 3]> = src.iter().map(|&p| unpack(p)).collect();
+    filter_linear(&linear, width, height, factor, filter).into_iter()
+        .map(|rgb| (linear_to_srgb(rgb[0]) << 16) + (linear_to_srgb(rgb[1]) << 8) + linear_to_srgb(rgb[2]))
+        .collect()
+}
+
+// The separable filter behind downsample, horizontal pass first, on samples
+// of N linear channels each.
+fn filter_linear<const N: usize>(src:    &[[f32; N]],
+                                 width:  usize,
+                                 height: usize,
+                                 factor: usize,
+                                 filter: DownsampleFilter) -> Vec<[f32; N]> {
+    let src_width  = width * factor;
+    let src_height = height * factor;
+    assert!(src.len() == src_width * src_height);
+
+    let mut rows = vec![[0.0f32; N]; width * src_height];
+    let x_taps   = filter.taps(width, factor);
+    for y in 0 .. src_height {
+        for x in 0 .. width {
+            let mut sum = [0.0; N];
+            for &(sx, w) in &x_taps[x] {
+                let sample = src[y * src_width + sx];
+                for c in 0..N { sum[c] += sample[c] * w; }
+            }
+            rows[y * width + x] = sum;
+        }
+    }
+
+    let mut dst = vec![[0.0f32; N]; width * height];
+    let y_taps  = filter.taps(height, factor);
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let mut sum = [0.0; N];
+            for &(sy, w) in &y_taps[y] {
+                let sample = rows[sy * width + x];
+                for c in 0..N { sum[c] += sample[c] * w; }
+            }
+            dst[y * width + x] = sum;
+        }
+    }
+    dst
+}
+
+/// Chainable configuration for a `Renderer`. Every setting defaults to what
+/// the interactive viewer uses, so only the differences need to be spelled out.
+///
+/// ```
+/// # use mandelbrot::{RendererBuilder, INITIAL_VIEW};
+/// let mut renderer = RendererBuilder::new()
+///     .size(640, 480)
+///     .limit(500)
+///     .threads(8)
+///     .build();
+/// let pixels = renderer.render(INITIAL_VIEW);
+/// assert_eq!(pixels.len(), 640 * 480);
+/// ```
+#[derive(Clone)]
+pub struct RendererBuilder {
+    width:       usize,
+    height:      usize,
+    settings:    RenderSettings,
+    palette:     Vec<(f32, f32, f32)>,
+    supersample: usize,
+    filter:      DownsampleFilter,
+    coloring:    ColoringMode,
+    error_color: Option<u32>,
+    gamma:       f32,
+}
+
+// Error pixels are magenta in debug builds and blend in as interior pixels in
+// release builds.
+const DEFAULT_ERROR_COLOR: u32 = 0x00FF_00FF;
+
+impl Default for RendererBuilder {
+    fn default() -> RendererBuilder {
+        RendererBuilder::new()
+    }
+}
+
+impl RendererBuilder {
+    pub fn new() -> RendererBuilder {
+        RendererBuilder {
+            width:       Config::default().width,
+            height:      Config::default().height,
+            settings:    RenderSettings::default(),
+            palette:     COLORS.to_vec(),
+            supersample: 1,
+            filter:      DownsampleFilter::Box,
+            coloring:    ColoringMode::Palette,
+            error_color: if cfg!(debug_assertions) { Some(DEFAULT_ERROR_COLOR) } else { None },
+            gamma:       DEFAULT_GAMMA,
+        }
+    }
+
+    pub fn size(mut self, width: usize, height: usize) -> RendererBuilder {
+        self.width  = width;
+        self.height = height;
+        self
+    }
+
+    pub fn config(self, config: &Config) -> RendererBuilder {
+        self.size(config.width, config.height).limit(config.limit).threads(config.num_threads).gamma(config.gamma)
+            .reference_bits(config.reference_bits)
+    }
+
+    pub fn limit(mut self, limit: u32) -> RendererBuilder {
+        self.settings.limit = limit;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> RendererBuilder {
+        self.settings.threads = cmp::max(threads, 1);
+        self
+    }
+
+    pub fn kernel(mut self, kernel: KernelWidth) -> RendererBuilder {
+        self.settings.kernel = kernel;
+        self
+    }
+
+    pub fn pixel_aspect(mut self, pixel_aspect: f64) -> RendererBuilder {
+        assert!(pixel_aspect > 0.0, "pixel aspect must be positive");
+        self.settings.pixel_aspect = pixel_aspect;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f64) -> RendererBuilder {
+        self.settings.rotation = rotation;
+        self
+    }
+
+    pub fn fractal(mut self, fractal: FractalKind) -> RendererBuilder {
+        self.settings.fractal = fractal;
+        self
+    }
+
+    pub fn tile_size(mut self, size: usize) -> RendererBuilder {
+        self.settings.tile_size = size;
+        self
+    }
+
+    pub fn limit_per_octave(mut self, iterations: u32) -> RendererBuilder {
+        self.settings.limit_per_octave = iterations;
+        self
+    }
+
+    pub fn reference_bits(mut self, bits: u32) -> RendererBuilder {
+        assert!(bits <= MAX_REFERENCE_BITS, "reference orbits take at most {} bits", MAX_REFERENCE_BITS);
+        self.settings.reference_bits = bits;
+        self
+    }
+
+    pub fn center(mut self, center: Option<PrecisePoint>) -> RendererBuilder {
+        self.settings.center = center.map(Arc::new);
+        self
+    }
+
+    pub fn window(mut self, frame: (usize, usize), origin: (usize, usize)) -> RendererBuilder {
+        self.settings.window = Some(FrameWindow { frame, origin });
+        self
+    }
+
+    pub fn jitter_seed(mut self, seed: u64) -> RendererBuilder {
+        self.settings.jitter_seed = seed;
+        self
+    }
+
+    pub fn formula(mut self, formula: Option<Formula>) -> RendererBuilder {
+        self.settings.formula = formula.and_then(|f| if f.is_quadratic() { None } else { Some(Arc::new(f)) });
+        self
+    }
+
+    pub fn supersample(mut self, factor: usize) -> RendererBuilder {
+        self.supersample = cmp::max(factor, 1);
+        self
+    }
+
+    pub fn downsample_filter(mut self, filter: DownsampleFilter) -> RendererBuilder {
+        self.filter = filter;
+        self
+    }
+
+    pub fn error_color(mut self, color: Option<u32>) -> RendererBuilder {
+        self.error_color = color;
+        self
+    }
+
+    pub fn coloring(mut self, coloring: ColoringMode) -> RendererBuilder {
+        self.coloring = coloring;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: f32) -> RendererBuilder {
+        assert!(gamma > 0.0, "gamma must be positive");
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn palette(mut self, palette: &[(f32, f32, f32)]) -> RendererBuilder {
+        assert!(palette.len() >= 2, "a palette needs at least two stops");
+        self.palette = palette.to_vec();
+        self
+    }
+
+    pub fn build(mut self) -> Renderer {
+        if !self.settings.kernel.supported() {
+            self.settings.kernel = KernelWidth::fastest();
+        }
+        let samples = self.width * self.height * self.supersample * self.supersample;
+        Renderer {
+            cache:        IterationCache::new(samples, self.settings.limit),
+            active_limit: self.settings.limit,
+            tile_timings: Vec::new(),
+            reference:    None,
+            width:        self.width,
+            height:       self.height,
+            settings:     self.settings,
+            palette:      PaletteLut::new(&self.palette, self.gamma),
+            stops:        self.palette,
+            gamma:        self.gamma,
+            supersample:  self.supersample,
+            filter:       self.filter,
+            coloring:     self.coloring,
+            error_color:  self.error_color,
+        }
+    }
+}
+
+/// Renders views at a fixed configuration, reusing its iteration cache
+/// between frames.
+pub struct Renderer {
+    pub width:       usize,
+    pub height:      usize,
+    settings:        RenderSettings,
+    palette:         PaletteLut,
+    pub supersample: usize,
+    filter:          DownsampleFilter,
+    coloring:        ColoringMode,
+    // None paints error pixels like interior ones.
+    error_color:     Option<u32>,
+    cache:           IterationCache,
+    // Limit the cached view was iterated to, see RenderSettings::limit_at.
+    active_limit:    u32,
+    // The stops `palette` was built from, for colorize_linear.
+    stops:           Vec<(f32, f32, f32)>,
+    // What `palette` was encoded for, so a replacement can match it.
+    gamma:           f32,
+    // Per-tile cost of the last iterate, in output pixels.
+    tile_timings:    Vec<TileTiming>,
+    // Set when the last iterate perturbed a reference orbit.
+    reference:       Option<ReferenceMark>,
+}
+
+impl Renderer {
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    // Settings only take effect with the next iterate; the cache keeps what
+    // the last one rendered until then.
+    pub fn set_settings(&mut self, settings: RenderSettings) {
+        self.settings = settings;
+    }
+
+    pub fn set_kernel(&mut self, kernel: KernelWidth) {
+        self.settings.kernel = kernel;
+    }
+
+    pub fn set_rotation(&mut self, rotation: f64) {
+        self.settings.rotation = rotation;
+    }
+
+    pub fn set_fractal(&mut self, fractal: FractalKind) {
+        self.settings.fractal = fractal;
+    }
+
+    pub fn set_limit(&mut self, limit: u32) {
+        self.settings.limit = limit;
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: usize) {
+        self.settings.tile_size = tile_size;
+    }
+
+    pub fn set_coloring(&mut self, coloring: ColoringMode) {
+        self.coloring = coloring;
+    }
+
+    // Rebuilds the lookup table at the gamma the renderer was built with.
+    pub fn set_palette(&mut self, palette: &[(f32, f32, f32)]) {
+        assert!(palette.len() >= 2, "a palette needs at least two stops");
+        self.palette = PaletteLut::new(palette, self.gamma);
+        self.stops   = palette.to_vec();
+    }
+
+    pub fn cache(&self) -> &IterationCache {
+        &self.cache
+    }
+
+    pub fn active_limit(&self) -> u32 {
+        self.active_limit
+    }
+
+    pub fn tile_timings(&self) -> &[TileTiming] {
+        &self.tile_timings
+    }
+
+    pub fn reference(&self) -> Option<ReferenceMark> {
+        self.reference
+    }
+
+    // Size of the iteration cache, which is the output size times the
+    // supersampling factor.
+    fn bounds(&self) -> (usize, usize) {
+        (self.width * self.supersample, self.height * self.supersample)
+    }
+
+    /// Renders `view` into a freshly allocated 0x00RRGGBB buffer of the
+    /// configured size, downsampling with the configured filter when
+    /// supersampling.
+    pub fn render(&mut self, view: View) -> Vec<u32> {
+        self.render_with(view, &CancelToken::new())
+    }
+
+    /// Starts rendering `view` on a background thread and returns at once;
+    /// see `RenderHandle`.
+    pub fn render_async(self, view: View) -> RenderHandle {
+        self.spawn_render(move |renderer, cancel| renderer.render_with(view, cancel))
+    }
+
+    // Runs `render` with the renderer on a background thread.
+    fn spawn_render<F>(self, render: F) -> RenderHandle
+        where F: FnOnce(&mut Renderer, &CancelToken) -> Vec<u32> + Send + 'static {
+        let cancel = CancelToken::new();
+        let pixels = self.bounds().0 * self.bounds().1;
+        let thread = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let mut renderer = self;
+                let pixels       = render(&mut renderer, &cancel);
+                (renderer, pixels)
+            })
+        };
+        RenderHandle { thread: Some(thread), result: None, cancel, pixels }
+    }
+
+    pub fn render_with(&mut self, view: View, cancel: &CancelToken) -> Vec<u32> {
+        let mut pixels = vec![0; self.width * self.height];
+        self.iterate(view, (0.0, 0.0), cancel);
+        self.colorize(&mut pixels);
+        pixels
+    }
+
+    // Instant stand-in for `view` at `rotation` while it renders: bilinearly
+    // resamples the cells still cached for `cached` (rendered with the
+    // current settings) into the new view's pixels and colors them with the
+    // current coloring. Pixels the old view didn't cover are clamped to its
+    // edge.
+    pub fn preview(&self, cached: View, view: View, rotation: f64, pixels: &mut [u32]) {
+        let bounds = self.bounds();
+        let output = (self.width, self.height);
+        let source = |i: usize| {
+            let point  = pixel_to_point(output, (i % output.0, i / output.0), view.upper_left, view.lower_right,
+                                        self.settings.pixel_aspect, rotation);
+            let (x, y) = point_to_pixel(bounds, point, cached.upper_left, cached.lower_right,
+                                        self.settings.pixel_aspect, self.settings.rotation);
+            (x.max(0.0), y.max(0.0))
+        };
+        let value  = self.coloring.cell_value();
+        let cells  = match self.cache {
+            IterationCache::Full(ref cells)    => resample(cells, bounds, output.0 * output.1, value, source),
+            IterationCache::Compact(ref cells) => resample(cells, bounds, output.0 * output.1, value, source),
+        };
+        self.colorize_cache(&IterationCache::Full(cells), pixels);
+    }
+
+    pub fn iterate(&mut self, view: View, jitter: (f32, f32), cancel: &CancelToken) {
+        let bounds               = self.bounds();
+        self.active_limit        = self.settings.limit_at(view.upper_left, view.lower_right);
+        self.settings.cell_value = self.coloring.cell_value();
+        self.settings.bailout    = self.coloring.bailout();
+        let settings = RenderSettings {
+            limit:  self.active_limit,
+            window: self.settings.window.map(|window| window.scaled(self.supersample)),
+            ..self.settings.clone()
+        };
+        let (timings, reference) = self.cache.render(bounds, view.upper_left, view.lower_right, &settings, jitter,
+                                                     cancel);
+        let scale    = |v: usize| v.div_ceil(self.supersample);
+        self.tile_timings = timings.into_iter().map(|t| TileTiming {
+            left:   t.left / self.supersample,
+            top:    t.top / self.supersample,
+            width:  scale(t.width),
+            height: scale(t.height),
+            ..t
+        }).collect();
+        // The reference is the center of the view, or of the frame around
+        // a window.
+        let (frame, origin) = self.settings.window.map(|w| (w.frame, w.origin))
+            .unwrap_or(((self.width, self.height), (0, 0)));
+        self.reference = reference.map(|iterations| ReferenceMark {
+            pixel: (frame.0 as f64 / 2.0 - origin.0 as f64, frame.1 as f64 / 2.0 - origin.1 as f64),
+            iterations,
+        });
+    }
+
+    // Colors the cached iterations into the output-sized `pixels`,
+    // downsampling when supersampling. Returns the number of error samples.
+    pub fn colorize(&self, pixels: &mut [u32]) -> usize {
+        if self.supersample == 1 {
+            return self.colorize_cache(&self.cache, pixels);
+        }
+        let (samples_w, samples_h) = self.bounds();
+        let mut samples = vec![0; samples_w * samples_h];
+        let errors      = self.colorize_cache(&self.cache, &mut samples);
+        pixels.copy_from_slice(&downsample(&samples, self.width, self.height, self.supersample, self.filter));
+        errors
+    }
+
+    // The cached iterations as linear-light Palette colors, whatever the
+    // coloring, plus the smooth count, per output pixel and downsampled like
+    // colorize. Nothing is rounded to 8 bits on the way. Error samples take
+    // the error color and a NaN count.
+    pub fn colorize_linear(&self) -> Vec<[f32; 4]> {
+        let error_color = self.error_color.unwrap_or_else(|| self.palette.color(self.active_limit as f32));
+        let error       = [srgb_to_linear((error_color >> 16) & 0xFF), srgb_to_linear((error_color >> 8) & 0xFF),
+                           srgb_to_linear(error_color & 0xFF), f32::NAN];
+        let samples     = match self.cache {
+            IterationCache::Full(ref cells)    => linear_samples(cells, &self.stops, self.gamma, error),
+            IterationCache::Compact(ref cells) => linear_samples(cells, &self.stops, self.gamma, error),
+        };
+        if self.supersample == 1 {
+            return samples;
+        }
+        filter_linear(&samples, self.width, self.height, self.supersample, self.filter)
+    }
+
+    fn colorize_cache(&self, cache: &IterationCache, pixels: &mut [u32]) -> usize {
+        let limit = self.active_limit;
+        match self.coloring {
+            ColoringMode::Palette => {
+                let error_color = self.error_color.unwrap_or_else(|| self.palette.color(limit as f32));
+                cache.colorize(pixels, &self.palette, error_color)
+            },
+            ColoringMode::Raw => {
+                cache.colorize_raw(pixels, limit, self.error_color.unwrap_or(0x00FF_FFFF))
+            },
+            ColoringMode::Histogram => {
+                let error_color = self.error_color.unwrap_or_else(|| self.palette.color(limit as f32));
+                cache.colorize_histogram(pixels, &self.palette, limit, error_color)
+            },
+            ColoringMode::OrbitTrap {..} => {
+                cache.colorize_trap(pixels, &self.palette, self.error_color.unwrap_or(0))
+            },
+            ColoringMode::Distance => {
+                cache.colorize_distance(pixels, limit, self.error_color.unwrap_or(0x00FF_0000))
+            },
+        }
+    }
+}
+
+/// A render started by `Renderer::render_async`.
+///
+/// The renderer moves onto one background thread, which splits the view into
+/// tiles over `settings.threads` crossbeam workers exactly like `render`, and
+/// is handed back by `join`. Cancellation is cooperative: workers check the
+/// token between tile rows, so `cancel` returns immediately and the threads
+/// wind down within a row. A panic on the render thread comes back from
+/// `poll` and `join` as an error with the panic message.
+pub struct RenderHandle {
+    thread: Option<thread::JoinHandle<(Renderer, Vec<u32>)>>,
+    // Set once the thread has been joined, so a panic is reported every time.
+    result: Option<Result<(Renderer, Vec<u32>), String>>,
+    cancel: CancelToken,
+    pixels: usize,
+}
+
+impl RenderHandle {
+    /// Returns the finished pixels without blocking, or None while the render
+    /// is running or after it was cancelled.
+    pub fn poll(&mut self) -> Result<Option<&[u32]>, String> {
+        if self.thread.as_ref().map(|t| t.is_finished()).unwrap_or(false) {
+            self.result = self.thread.take().map(join_render);
+        }
+        match self.result {
+            Some(Err(ref e))                => Err(e.clone()),
+            _ if self.cancel.is_cancelled() => Ok(None),
+            Some(Ok((_, ref pixels)))       => Ok(Some(pixels.as_slice())),
+            None                            => Ok(None),
+        }
+    }
+
+    /// Fraction of pixels iterated so far, 0.0 ..= 1.0.
+    pub fn progress(&self) -> f32 {
+        (self.cancel.pixels_done() as f32 / cmp::max(self.pixels, 1) as f32).min(1.0)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Blocks until the render ends and returns the renderer, with the pixels
+    /// unless it was cancelled.
+    pub fn join(mut self) -> Result<(Renderer, Option<Vec<u32>>), String> {
+        let result = match (self.result.take(), self.thread.take()) {
+            (Some(result), _) => result,
+            (None, Some(t))   => join_render(t),
+            (None, None)      => Err("render thread already joined".to_string()),
+        };
+        let (renderer, pixels) = result?;
+        Ok((renderer, if self.cancel.is_cancelled() { None } else { Some(pixels) }))
+    }
+}
+
+fn join_render(thread: thread::JoinHandle<(Renderer, Vec<u32>)>) -> Result<(Renderer, Vec<u32>), String> {
+    thread.join().map_err(|panic| {
+        let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        format!("render thread panicked: {}", message)
+    })
+}
+
+/// Renders the Mandelbrot set between `upper_left` and `lower_right` into a
+/// `bounds.0` x `bounds.1` buffer of 0x00RRGGBB pixels, with every other
+/// setting at its `RendererBuilder` default.
+///
+/// ```
+/// # extern crate mandelbrot;
+/// # extern crate num;
+/// # use num::Complex;
+/// # fn main() {
+/// let pixels = mandelbrot::render_mandelbrot((64, 48), Complex::new(-2.2, 1.0), Complex::new(1.2, -1.0), 100);
+/// assert_eq!(pixels.len(), 64 * 48);
+/// # }
+/// ```
+pub fn render_mandelbrot(bounds:      (usize, usize),
+                         upper_left:  Complex<f64>,
+                         lower_right: Complex<f64>,
+                         limit:       u32) -> Vec<u32> {
+    RendererBuilder::new()
+        .size(bounds.0, bounds.1)
+        .limit(limit)
+        .build()
+        .render(View { upper_left, lower_right })
+}
+
+// Row kernels sample `line.len()` points starting `first` columns past the
+// frame row's `origin` and advancing by `step` per column; `step.1` is only
+// non-zero for rotated views. Counting from the frame's left edge keeps every
+// sample the same however the frame is cut into tiles. Each point is the
+// starting z; c is the point itself, or the fixed Julia constant.
+fn render_row_scalar<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                                   jitter_x: f32, limit: u32, fractal: FractalKind, bailout: f32) {
+    for column in 0 .. line.len() {
+        let x = origin.0 + step.0 * ((first + column) as f32 + jitter_x);
+        let y = origin.1 + step.1 * ((first + column) as f32 + jitter_x);
+        let (count, magnitude) = match fractal {
+            FractalKind::Mandelbrot  => mandelbrot_scalar(x, y, x, y, limit, bailout),
+            FractalKind::Julia { c } => mandelbrot_scalar(x, y, c.re as f32, c.im as f32, limit, bailout),
+            FractalKind::BurningShip => burning_ship_scalar(x, y, limit, bailout),
+        };
+        let (count, fraction)  = smooth_count(count, magnitude, limit);
+        line[column] = C::pack(count, fraction);
+    }
+}
+
+fn render_row_x4<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                               jitter_x: f32, limit: u32, fractal: FractalKind, bailout: f32) {
+    let lanes    = f32x4::from_array([0., 1., 2., 3.]) + f32x4::splat(jitter_x);
+    let adjust_x = f32x4::splat(step.0) * lanes;
+    let adjust_y = f32x4::splat(step.1) * lanes;
+    for column in (0 .. line.len()).step_by(4) {
+        let x = f32x4::splat(origin.0 + step.0 * (first + column) as f32) + adjust_x;
+        let y = f32x4::splat(origin.1 + step.1 * (first + column) as f32) + adjust_y;
+        let (counts, magnitudes) = match fractal {
+            FractalKind::Mandelbrot  => mandelbrot_vector(x, y, x, y, limit, bailout),
+            FractalKind::Julia { c } => {
+                julia_vector(x, y, Complex { re: c.re as f32, im: c.im as f32 }, limit, bailout)
+            },
+            FractalKind::BurningShip => burning_ship_vector(x, y, limit, bailout),
+        };
+        for k in 0 .. cmp::min(4, line.len() - column) {
+            let (count, fraction) = smooth_count(counts[k], magnitudes[k], limit);
+            line[column + k] = C::pack(count, fraction);
+        }
+    }
+}
+
+// Stores the trap distance, clamped to 0 ..= 1, in place of the escape
+// fraction; lanes that escape at once never get near the trap.
+fn render_row_trap<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                                 jitter_x: f32, limit: u32, fractal: FractalKind, shape: TrapShape, bailout: f32) {
+    let lanes    = f32x4::from_array([0., 1., 2., 3.]) + f32x4::splat(jitter_x);
+    let adjust_x = f32x4::splat(step.0) * lanes;
+    let adjust_y = f32x4::splat(step.1) * lanes;
+    for column in (0 .. line.len()).step_by(4) {
+        let x = f32x4::splat(origin.0 + step.0 * (first + column) as f32) + adjust_x;
+        let y = f32x4::splat(origin.1 + step.1 * (first + column) as f32) + adjust_y;
+        let (c_x, c_y) = fractal.julia_c().map(|(re, im)| (f32x4::splat(re), f32x4::splat(im))).unwrap_or((x, y));
+        let fold              = fractal == FractalKind::BurningShip;
+        let (counts, nearest) = orbit_trap_vector(x, y, c_x, c_y, limit, shape, fold, bailout);
+        for k in 0 .. cmp::min(4, line.len() - column) {
+            line[column + k] = C::pack(counts[k], nearest[k].min(1.0));
+        }
+    }
+}
+
+// Stores the distance estimate |z| ln|z| / |dz| in pixels, clamped to 0 ..= 1,
+// in place of the escape fraction. A derivative that overflowed means the
+// point hugs the boundary, so it lands on 0.
+fn render_row_distance<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                                     jitter_x: f32, limit: u32, fractal: FractalKind, bailout: f32) {
+    let lanes    = f32x4::from_array([0., 1., 2., 3.]) + f32x4::splat(jitter_x);
+    let adjust_x = f32x4::splat(step.0) * lanes;
+    let adjust_y = f32x4::splat(step.1) * lanes;
+    let pixel    = (step.0 * step.0 + step.1 * step.1).sqrt();
+    let dc       = if fractal.julia_c().is_some() { 0.0 } else { 1.0 };
+    for column in (0 .. line.len()).step_by(4) {
+        let x = f32x4::splat(origin.0 + step.0 * (first + column) as f32) + adjust_x;
+        let y = f32x4::splat(origin.1 + step.1 * (first + column) as f32) + adjust_y;
+        let (c_x, c_y) = fractal.julia_c().map(|(re, im)| (f32x4::splat(re), f32x4::splat(im))).unwrap_or((x, y));
+        let (counts, magnitudes, derivatives) =
+            distance_vector(x, y, c_x, c_y, limit, dc, fractal == FractalKind::BurningShip, bailout);
+        for k in 0 .. cmp::min(4, line.len() - column) {
+            let (magnitude, derivative) = (magnitudes[k], derivatives[k]);
+            let distance = 0.5 * (magnitude / derivative).sqrt() * magnitude.ln() / pixel;
+            let distance = if distance.is_nan() { 0.0 } else { distance.max(0.0).min(1.0) };
+            line[column + k] = C::pack(counts[k], distance);
+        }
+    }
+}
+
+fn render_row_formula<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                                    jitter_x: f32, limit: u32, fractal: FractalKind, formula: &Formula,
+                                    bailout: f32) {
+    let lanes     = f32x4::from_array([0., 1., 2., 3.]) + f32x4::splat(jitter_x);
+    let adjust_x  = f32x4::splat(step.0) * lanes;
+    let adjust_y  = f32x4::splat(step.1) * lanes;
+    let mut stack = Vec::with_capacity(formula.ops.len());
+    for column in (0 .. line.len()).step_by(4) {
+        let x = f32x4::splat(origin.0 + step.0 * (first + column) as f32) + adjust_x;
+        let y = f32x4::splat(origin.1 + step.1 * (first + column) as f32) + adjust_y;
+        let (c_x, c_y) = fractal.julia_c().map(|(re, im)| (f32x4::splat(re), f32x4::splat(im))).unwrap_or((x, y));
+        let points = mandelbrot_formula(formula, (x, y), c_x, c_y, limit, bailout, &mut stack);
+        for k in 0 .. cmp::min(4, line.len() - column) {
+            line[column + k] = C::pack(points[k], 0.0);
+        }
+    }
+}
+
+fn render_row_deep<C: CacheCell>(line: &mut [C], first: usize, origin: (f64, f64), step: (f64, f64),
+                                 jitter_x: f32, limit: u32, fractal: FractalKind, bailout: f32) {
+    for column in 0 .. line.len() {
+        let x = origin.0 + step.0 * ((first + column) as f64 + jitter_x as f64);
+        let y = origin.1 + step.1 * ((first + column) as f64 + jitter_x as f64);
+        let (count, magnitude) = match fractal {
+            FractalKind::Mandelbrot  => mandelbrot_f64(x, y, x, y, limit, bailout),
+            FractalKind::Julia { c } => mandelbrot_f64(x, y, c.re, c.im, limit, bailout),
+            FractalKind::BurningShip => burning_ship_f64(x, y, limit, bailout),
+        };
+        let (count, fraction)  = smooth_count(count, magnitude, limit);
+        line[column] = C::pack(count, fraction);
+    }
+}
+
+// Like render_row_deep, but `origin` and `step` are offsets from the
+// reference point rather than plane coordinates. Returns how many pixels
+// glitched and were iterated again on their own.
+fn render_row_perturbation<C: CacheCell>(line: &mut [C], first: usize, origin: (f64, f64), step: (f64, f64),
+                                         jitter_x: f32, limit: u32, reference: &ReferenceOrbit) -> u32 {
+    let mut glitches = 0;
+    for column in 0 .. line.len() {
+        let offset = Complex {
+            re: origin.0 + step.0 * ((first + column) as f64 + jitter_x as f64),
+            im: origin.1 + step.1 * ((first + column) as f64 + jitter_x as f64),
+        };
+        let (count, magnitude) = reference.iterate(offset, limit).unwrap_or_else(|| {
+            glitches += 1;
+            reference.iterate_exact(offset, limit)
+        });
+        let (count, fraction)  = smooth_count(count, magnitude, limit);
+        line[column] = C::pack(count, fraction);
+    }
+    glitches
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx,avx2")]
+unsafe fn render_row_x8<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                                      jitter_x: f32, limit: u32, fractal: FractalKind, bailout: f32) {
+    let lanes    = f32x8::from_array([0., 1., 2., 3., 4., 5., 6., 7.]) + f32x8::splat(jitter_x);
+    let adjust_x = f32x8::splat(step.0) * lanes;
+    let adjust_y = f32x8::splat(step.1) * lanes;
+    for column in (0 .. line.len()).step_by(8) {
+        let x = f32x8::splat(origin.0 + step.0 * (first + column) as f32) + adjust_x;
+        let y = f32x8::splat(origin.1 + step.1 * (first + column) as f32) + adjust_y;
+        let (counts, magnitudes) = match fractal {
+            FractalKind::Mandelbrot  => mandelbrot_vector8(x, y, x, y, limit, bailout),
+            FractalKind::Julia { c } => {
+                mandelbrot_vector8(x, y, f32x8::splat(c.re as f32), f32x8::splat(c.im as f32), limit, bailout)
+            },
+            FractalKind::BurningShip => burning_ship_vector8(x, y, limit, bailout),
+        };
+        for k in 0 .. cmp::min(8, line.len() - column) {
+            let (count, fraction) = smooth_count(counts[k], magnitudes[k], limit);
+            line[column + k] = C::pack(count, fraction);
+        }
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+unsafe fn render_row_x8<C: CacheCell>(line: &mut [C], first: usize, origin: (f32, f32), step: (f32, f32),
+                                      jitter_x: f32, limit: u32, fractal: FractalKind, bailout: f32) {
+    render_row_x4(line, first, origin, step, jitter_x, limit, fractal, bailout)
+}
+
+// Pixel steps below this many f32 ulps of the largest coordinate in view
+// render on the f64 scalar path: past it neighbouring pixels round to the
+// same f32 point and the image turns blocky.
+const DEEP_ZOOM_ULPS: f64 = 8.0;
+
+fn is_deep_zoom(width_step: f64, height_step: f64, upper_left: Complex<f64>, lower_right: Complex<f64>) -> bool {
+    let extent = upper_left.re.abs().max(upper_left.im.abs())
+        .max(lower_right.re.abs()).max(lower_right.im.abs());
+    let ulp = f32::EPSILON as f64 * extent.max(1.0);
+    width_step.abs().min(height_step.abs()) < DEEP_ZOOM_ULPS * ulp
+}
+
+// The same test against f64, past which even the f64 rows go blocky.
+const PERTURBATION_ULPS: f64 = 64.0;
+
+fn is_perturbation_zoom(width_step: f64, height_step: f64, upper_left: Complex<f64>, lower_right: Complex<f64>) -> bool {
+    let extent = upper_left.re.abs().max(upper_left.im.abs())
+        .max(lower_right.re.abs()).max(lower_right.im.abs());
+    let ulp = f64::EPSILON * extent.max(1.0);
+    width_step.abs().min(height_step.abs()) < PERTURBATION_ULPS * ulp
+}
+
+// Renders one tile given as its rows, its top left pixel at `first` in a
+// `bounds` frame. `upper_left` and `lower_right` are the plane points at the
+// frame's corners, or their offsets from the reference point when there is a
+// `reference` orbit to perturb. Returns the number of glitched pixels.
+#[inline(never)]
+fn render<C: CacheCell>(lines:       &mut [&mut [C]],
+                        bounds:      (usize, usize),
+                        first:       (usize, usize),
+                        upper_left:  Complex<f64>,
+                        lower_right: Complex<f64>,
+                        settings:    &RenderSettings,
+                        reference:   Option<&ReferenceOrbit>,
+                        jitter:      (f32, f32),
+                        cancel:      &CancelToken) -> u32 {
+
+    let width = lines.first().map(|line| line.len()).unwrap_or(0);
+    assert!(lines.iter().all(|line| line.len() == width));
+
+    let (left, top)      = (upper_left.re, upper_left.im);
+    // The corners are already rotated; undo the rotation on the diagonal to
+    // get the axis-aligned pixel steps, then turn the step vectors back. With
+    // no rotation this reduces exactly to the axis-aligned steps.
+    let (sin, cos)       = settings.rotation.sin_cos();
+    let (dx, dy)         = (lower_right.re - left, lower_right.im - top);
+    let width_step:  f64 = (dx * cos + dy * sin) / bounds.0 as f64;
+    let height_step: f64 = (dy * cos - dx * sin) / bounds.1 as f64;
+    let column_step      = (width_step * cos, width_step * sin);
+    let row_step         = (-height_step * sin, height_step * cos);
+    let deep             = is_deep_zoom(width_step, height_step, upper_left, lower_right);
+    // A kernel the CPU lacks would fault, so fall back to the scalar loop.
+    let kernel           = if settings.kernel.supported() { settings.kernel } else { KernelWidth::Scalar };
+
+    let mut glitches = 0;
+    for (row, line) in lines.iter_mut().enumerate() {
+        if cancel.is_cancelled() { return glitches }
+        let offset  = (first.1 + row) as f64 + jitter.1 as f64;
+        let limit   = settings.limit;
+        let fractal = settings.fractal;
+        // Orbit traps and distance estimates only have the f32x4 loop, at any
+        // depth.
+        let escape  = settings.cell_value == CellValue::Escape;
+        if escape && (reference.is_some() || (deep && settings.formula.is_none())) {
+            let origin = (left + row_step.0 * offset, top + row_step.1 * offset);
+            match reference {
+                Some(reference) => {
+                    glitches += render_row_perturbation(line, first.0, origin, column_step, jitter.0, limit,
+                                                        reference)
+                },
+                None            => {
+                    render_row_deep(line, first.0, origin, column_step, jitter.0, limit, fractal, settings.bailout)
+                },
+            }
+            cancel.finish_pixels(line.len());
+            continue;
+        }
+        let origin      = ((left + row_step.0 * offset) as f32, (top + row_step.1 * offset) as f32);
+        let column_step = (column_step.0 as f32, column_step.1 as f32);
+        let bailout     = settings.bailout;
+        if let Some(ref formula) = settings.formula {
+            render_row_formula(line, first.0, origin, column_step, jitter.0, limit, fractal, formula, bailout);
+        } else if let CellValue::Trap(shape) = settings.cell_value {
+            render_row_trap(line, first.0, origin, column_step, jitter.0, limit, fractal, shape, bailout);
+        } else if settings.cell_value == CellValue::Distance {
+            render_row_distance(line, first.0, origin, column_step, jitter.0, limit, fractal, bailout);
+        } else {
+            match kernel {
+                KernelWidth::Scalar => {
+                    render_row_scalar(line, first.0, origin, column_step, jitter.0, limit, fractal, bailout)
+                },
+                KernelWidth::X4     => {
+                    render_row_x4(line, first.0, origin, column_step, jitter.0, limit, fractal, bailout)
+                },
+                // Only selected once supported() has seen AVX2 at runtime.
+                KernelWidth::X8     => unsafe {
+                    render_row_x8(line, first.0, origin, column_step, jitter.0, limit, fractal, bailout)
+                },
+            }
+        }
+        cancel.finish_pixels(line.len());
+    }
+    glitches
+}
+
+// Default tile edge in pixels. Small enough that the cheap interior and the
+// expensive boundary of a view spread over all workers.
+const TILE_SIZE: usize = 64;
+
+// Where one tile of a render lies in the cache and how long it took. On the
+// perturbation path also how many of its pixels glitched, and how many more
+// iterations they took on average than the reference point; both stay 0
+// elsewhere.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TileTiming {
+    pub left:     usize,
+    pub top:      usize,
+    pub width:    usize,
+    pub height:   usize,
+    pub ms:       f32,
+    pub glitches: u32,
+    pub delta:    f32,
+}
+
+// Where the perturbation reference of the last iterate sat, in output
+// pixels, and how many iterations its own orbit ran.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReferenceMark {
+    pub pixel:      (f64, f64),
+    pub iterations: u32,
+}
+
+fn render_parallel<C: CacheCell>(cells:       &mut [C],
+                                 bounds:      (usize, usize),
+                                 upper_left:  Complex<f64>,
+                                 lower_right: Complex<f64>,
+                                 settings:    &RenderSettings,
+                                 jitter:      (f32, f32),
+                                 cancel:      &CancelToken) -> (Vec<TileTiming>, Option<u32>) {
+    // An empty frame has no rows to cut into tiles.
+    if bounds.0 == 0 || bounds.1 == 0 {
+        return (Vec::new(), None);
+    }
+    // Cut the cells into tile x tile squares, each one the row segments it
+    // covers, and let the workers pull them off a shared queue until it runs
+    // dry. A worker stuck on a boundary tile then no longer holds up a whole
+    // band of the image.
+    let tile     = if settings.tile_size == 0 { TILE_SIZE } else { settings.tile_size };
+    let columns  = bounds.0.div_ceil(tile);
+    // The cells are a window of this frame, or all of it.
+    let (frame, origin) = settings.window.map(|w| (w.frame, w.origin)).unwrap_or((bounds, (0, 0)));
+    // Views too deep for f64 pixel coordinates perturb one high-precision
+    // orbit of the center instead; the tiles then work in offsets from it.
+    let step      = ((lower_right.re - upper_left.re) / frame.0 as f64,
+                     (upper_left.im - lower_right.im) / frame.1 as f64);
+    let offsets   = View { upper_left, lower_right };
+    let placed    = settings.center.as_ref().map(|center| center.to_f64()).unwrap_or_default();
+    let (upper_left, lower_right) = (upper_left + placed, lower_right + placed);
+    let perturb   = settings.formula.is_none() && settings.cell_value == CellValue::Escape
+        && is_perturbation_zoom(step.0, step.1, upper_left, lower_right);
+    let reference = match settings.center {
+        _ if !perturb    => None,
+        Some(ref center) => ReferenceOrbit::precise(center, settings.fractal, settings.limit, settings.bailout),
+        None             => ReferenceOrbit::new(view_center(offsets), settings.fractal, settings.limit,
+                                                settings.reference_bits, settings.bailout),
+    };
+    // Every tile maps its pixels from the frame's corners, so how the frame
+    // is cut never changes a sample. Offsets are taken from the view center,
+    // which sits that far from a precise reference.
+    let corner = if reference.is_some() { pixel_offset } else { pixel_to_point };
+    let (upper_left, lower_right, shift) = match settings.center {
+        Some(_) if reference.is_some() => (offsets.upper_left, offsets.lower_right, view_center(offsets)),
+        _                              => (upper_left, lower_right, Complex { re: 0.0, im: 0.0 }),
+    };
+    let frame_upper = corner(frame, (0, 0), upper_left, lower_right, settings.pixel_aspect, settings.rotation) + shift;
+    let frame_lower = corner(frame, frame, upper_left, lower_right, settings.pixel_aspect, settings.rotation) + shift;
+    let tiles       = SegQueue::new();
+    let timings     = SegQueue::new();
+    for (band, rows) in cells.chunks_mut(tile * bounds.0).enumerate() {
+        let mut segments: Vec<Vec<&mut [C]>> = (0 .. columns).map(|_| Vec::with_capacity(tile)).collect();
+        for line in rows.chunks_mut(bounds.0) {
+            for (segment, part) in segments.iter_mut().zip(line.chunks_mut(tile)) {
+                segment.push(part);
+            }
+        }
+        for (column, lines) in segments.into_iter().enumerate() {
+            tiles.push(((column * tile, band * tile), lines));
+        }
+    }
+    crossbeam::scope(|spawner| {
+        for _ in 0 .. settings.threads {
+            let tiles     = &tiles;
+            let timings   = &timings;
+            let reference = &reference;
+            spawner.spawn(move || {
+                while let Some(((left, top), mut lines)) = tiles.try_pop() {
+                    let start  = Instant::now();
+                    let width  = lines[0].len();
+                    let height = lines.len();
+                    let first    = (origin.0 + left, origin.1 + top);
+                    let glitches = render(&mut lines, frame, first, frame_upper, frame_lower, settings,
+                                          reference.as_ref(), jitter, cancel);
+                    let ms    = start.elapsed().as_secs_f32() * 1000.0;
+                    let delta = reference.as_ref().map(|reference| {
+                        let total: u64 = lines.iter().flat_map(|line| line.iter())
+                            .map(|cell| cell.count() as u64).sum();
+                        total as f32 / (width * height) as f32 - reference.iterations() as f32
+                    }).unwrap_or(0.0);
+                    timings.push(TileTiming { left, top, width, height, ms, glitches, delta });
+                }
+            });
+        }
+    });
+    let mut collected = Vec::new();
+    while let Some(timing) = timings.try_pop() {
+        collected.push(timing);
+    }
+    (collected, reference.map(|reference| reference.iterations()))
+}
+
+const TILE_CANDIDATES: &'static [usize] = &[16, 32, 64, 128, 256];
+// Frames rendered with the chosen tile size before probing again.
+const TILE_REPROBE_FRAMES: u32 = 120;
+
+// Tries each candidate tile size for one frame, keeps the fastest and
+// re-probes periodically, so the choice follows the view's cost profile.
+// Probe frames are regular frames at another size, so probing costs only the
+// difference between candidates.
+pub struct TileTuner {
+    probe:   Option<usize>,
+    timings: Vec<f32>,
+    best:    usize,
+    frames:  u32,
+}
+
+impl Default for TileTuner {
+    fn default() -> TileTuner {
+        TileTuner::new()
+    }
+}
+
+impl TileTuner {
+    pub fn new() -> TileTuner {
+        TileTuner { probe: Some(0), timings: Vec::new(), best: TILE_CANDIDATES[0], frames: 0 }
+    }
+
+    pub fn tile_size(&self) -> usize {
+        self.probe.map(|i| TILE_CANDIDATES[i]).unwrap_or(self.best)
+    }
+
+    // Returns the tile size a finished probe round settled on.
+    pub fn record(&mut self, ms: f32) -> Option<usize> {
+        match self.probe {
+            Some(i) => {
+                self.timings.push(ms);
+                if i + 1 < TILE_CANDIDATES.len() {
+                    self.probe = Some(i + 1);
+                    return None;
+                }
+                let fastest = (0 .. self.timings.len())
+                    .min_by(|&a, &b| self.timings[a].partial_cmp(&self.timings[b]).unwrap_or(cmp::Ordering::Equal))
+                    .unwrap_or(0);
+                self.best   = TILE_CANDIDATES[fastest];
+                self.probe  = None;
+                self.frames = 0;
+                self.timings.clear();
+                Some(self.best)
+            },
+            None => {
+                self.frames += 1;
+                if self.frames >= TILE_REPROBE_FRAMES {
+                    self.probe = Some(0);
+                }
+                None
+            },
+        }
+    }
+}
+
+pub const DEFAULT_JITTER_SEED: u64 = 0x5EED;
+
+pub const DEFAULT_JULIA_C: Complex<f64> = Complex { re: -0.8, im: 0.156 };
+
+pub fn view_center(view: View) -> Complex<f64> {
+    Complex {
+        re: (view.upper_left.re + view.lower_right.re) / 2.0,
+        im: (view.upper_left.im + view.lower_right.im) / 2.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test::{black_box, Bencher};
+
+    const UPPER_LEFT:  Complex<f64> = Complex { re: -1.0, im:  1.0 };
+    const LOWER_RIGHT: Complex<f64> = Complex { re:  1.0, im: -1.0 };
+
+    fn point(pixel: (usize, usize)) -> Complex<f64> {
+        pixel_to_point((100, 100), pixel, UPPER_LEFT, LOWER_RIGHT, 1.0, 0.0)
+    }
+
+    #[test]
+    fn pixel_to_point_center() {
+        assert_eq!(point((50, 50)), Complex { re: 0.0, im: 0.0 });
+    }
+
+    #[test]
+    fn pixel_to_point_corners() {
+        assert_eq!(point((0, 0)),     UPPER_LEFT);
+        assert_eq!(point((100, 100)), LOWER_RIGHT);
+        assert_eq!(point((100, 0)),   Complex { re: 1.0,  im: 1.0 });
+        assert_eq!(point((0, 100)),   Complex { re: -1.0, im: -1.0 });
+        // The last pixel inside the image is one step short of the corner.
+        assert_eq!(point((99, 99)),   Complex { re: 0.98, im: -0.98 });
+    }
+
+    #[test]
+    fn pixel_to_point_mid_edges() {
+        assert_eq!(point((50, 0)),   Complex { re: 0.0,  im: 1.0 });
+        assert_eq!(point((0, 50)),   Complex { re: -1.0, im: 0.0 });
+        assert_eq!(point((100, 50)), Complex { re: 1.0,  im: 0.0 });
+        assert_eq!(point((50, 100)), Complex { re: 0.0,  im: -1.0 });
+    }
+
+    // Renders a whole `bounds` view on the calling thread, one tile of
+    // full-width rows.
+    fn render_cells(bounds: (usize, usize), view: View, settings: &RenderSettings) -> Vec<FullCell> {
+        let mut cells: Vec<FullCell> = vec![FullCell::default(); bounds.0 * bounds.1];
+        {
+            let mut lines: Vec<&mut [FullCell]> = cells.chunks_mut(bounds.0).collect();
+            render(&mut lines, bounds, (0, 0), view.upper_left, view.lower_right, settings, None, (0.0, 0.0),
+                   &CancelToken::new());
+        }
+        cells
+    }
+
+    // Every cell of the image lies in exactly one tile, for sizes that do and
+    // do not divide the image and more threads than tiles.
+    #[test]
+    fn tiles_cover_every_cell_once() {
+        for &(bounds, tile, threads) in &[((100, 70), 16, 3), ((64, 64), 64, 1), ((33, 7), 5, 8), ((1, 1), 64, 2)] {
+            let settings = RenderSettings { limit: 16, tile_size: tile, threads, ..RenderSettings::default() };
+            let mut cells: Vec<FullCell> = vec![FullCell::default(); bounds.0 * bounds.1];
+            let (tiles, _) = render_parallel(&mut cells, bounds, INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right,
+                                             &settings, (0.0, 0.0), &CancelToken::new());
+            let mut covered = vec![0; bounds.0 * bounds.1];
+            for t in &tiles {
+                assert!(t.width > 0 && t.height > 0 && t.width <= tile && t.height <= tile);
+                for y in t.top .. t.top + t.height {
+                    for x in t.left .. t.left + t.width {
+                        covered[y * bounds.0 + x] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&n| n == 1), "{:?} tile {}", bounds, tile);
+        }
+    }
+
+    // Tiles sample from the frame's corners, so splitting the image must not
+    // move a single sample. Tile edges are a multiple of the widest kernel's
+    // lanes, as every tuner candidate is.
+    #[test]
+    fn parallel_render_matches_single_thread() {
+        let bounds = (190, 130);
+        for &kernel in &[KernelWidth::Scalar, KernelWidth::X4, KernelWidth::fastest()] {
+            let settings = RenderSettings {
+                limit: 200, tile_size: 32, threads: 4, kernel, ..RenderSettings::default()
+            };
+            let single   = render_cells(bounds, INITIAL_VIEW, &settings);
+            let mut tiled: Vec<FullCell> = vec![FullCell::default(); bounds.0 * bounds.1];
+            render_parallel(&mut tiled, bounds, INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right, &settings,
+                            (0.0, 0.0), &CancelToken::new());
+            for (i, (a, b)) in single.iter().zip(&tiled).enumerate() {
+                assert_eq!((a.count(), a.fraction()), (b.count(), b.fraction()),
+                           "{:?} pixel {:?}", kernel, (i % bounds.0, i / bounds.0));
+            }
+        }
+    }
+
+    // Points of the initial view on a grid, in groups of `lanes`.
+    fn grid_lanes(lanes: usize) -> Vec<Vec<(f32, f32)>> {
+        let bounds = (120, 80);
+        let points: Vec<(f32, f32)> = (0 .. bounds.1).flat_map(|y| (0 .. bounds.0).map(move |x| {
+            let p = pixel_to_point(bounds, (x, y), INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right, 1.0, 0.0);
+            (p.re as f32, p.im as f32)
+        })).collect();
+        points.chunks(lanes).map(|c| c.to_vec()).collect()
+    }
+
+    // The cardioid and cycle shortcuts only ever skip to the count the plain
+    // loop reaches anyway.
+    #[test]
+    fn vector_counts_match_scalar() {
+        let limit = 500;
+        for group in grid_lanes(4) {
+            let x = f32x4::from_array([group[0].0, group[1].0, group[2].0, group[3].0]);
+            let y = f32x4::from_array([group[0].1, group[1].1, group[2].1, group[3].1]);
+            let (counts, magnitudes) = mandelbrot_vector(x, y, x, y, limit, ESCAPE_BAILOUT);
+            for (k, &(x, y)) in group.iter().enumerate() {
+                let (count, magnitude) = mandelbrot_scalar(x, y, x, y, limit, ESCAPE_BAILOUT);
+                assert_eq!((counts[k], magnitudes[k]), (count, magnitude), "point {:?}", (x, y));
+            }
+        }
+    }
+
+    // Skipped on CPUs without AVX2, where X8 is never selected.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn wide_counts_match_narrow_and_scalar() {
+        if !KernelWidth::X8.supported() { return }
+        let limit = 500;
+        for group in grid_lanes(8) {
+            let x = f32x8::from_array([0, 1, 2, 3, 4, 5, 6, 7].map(|k| group[k].0));
+            let y = f32x8::from_array([0, 1, 2, 3, 4, 5, 6, 7].map(|k| group[k].1));
+            let wide      = unsafe { mandelbrot_vector8(x, y, x, y, limit, ESCAPE_BAILOUT) };
+            let wide_ship = unsafe { burning_ship_vector8(x, y, limit, ESCAPE_BAILOUT) };
+            for half in 0 .. 2 {
+                let x4 = f32x4::from_slice(&x.as_array()[half * 4 ..]);
+                let y4 = f32x4::from_slice(&y.as_array()[half * 4 ..]);
+                let narrow      = mandelbrot_vector(x4, y4, x4, y4, limit, ESCAPE_BAILOUT);
+                let narrow_ship = burning_ship_vector(x4, y4, limit, ESCAPE_BAILOUT);
+                for k in 0 .. 4 {
+                    let (px, py) = group[half * 4 + k];
+                    let scalar   = mandelbrot_scalar(px, py, px, py, limit, ESCAPE_BAILOUT);
+                    let ship     = burning_ship_scalar(px, py, limit, ESCAPE_BAILOUT);
+                    assert_eq!(wide.0[half * 4 + k], narrow.0[k], "point {:?}", (px, py));
+                    assert_eq!(wide.0[half * 4 + k], scalar.0, "point {:?}", (px, py));
+                    assert_eq!(wide_ship.0[half * 4 + k], narrow_ship.0[k], "point {:?}", (px, py));
+                    assert_eq!(wide_ship.0[half * 4 + k], ship.0, "point {:?}", (px, py));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn palette_index_wraps_negative_zero_and_large() {
+        assert_eq!(palette_index(0.0, 4), (0, 1, 0.0));
+        assert_eq!(palette_index(2.5, 4), (2, 3, 0.5));
+        assert_eq!(palette_index(-0.25, 4), (3, 0, 0.75));
+        assert_eq!(palette_index(-5.5, 4), (2, 3, 0.5));
+        assert_eq!(palette_index(4097.25, 4), (1, 2, 0.25));
+        assert_eq!(palette_index(1.0e9, 4), (0, 1, 0.0));
+        for &val in &[1.0e30, -1.0e30, f32::MAX, f32::MIN] {
+            let (left, right, p) = palette_index(val, 5);
+            assert!(left < 5 && right == (left + 1) % 5 && (0.0 .. 1.0).contains(&p), "{} -> {}", val, left);
+        }
+    }
+
+    #[test]
+    fn pixel_aspect_two_doubles_horizontal_step() {
+        let at     = |pixel, aspect| pixel_to_point((100, 100), pixel, UPPER_LEFT, LOWER_RIGHT, aspect, 0.0);
+        let step_x = (at((51, 50), 2.0) - at((50, 50), 2.0)).re;
+        let step_y = (at((50, 50), 2.0) - at((50, 51), 2.0)).im;
+        assert!((step_x - 2.0 * step_y).abs() < 1e-12, "{} vs {}", step_x, step_y);
+        assert!((step_y - 0.02).abs() < 1e-12);
+        // The stretch is around the view center, which stays put.
+        assert_eq!(at((50, 50), 2.0), Complex { re: 0.0, im: 0.0 });
+    }
+
+    #[test]
+    fn zero_rotation_matches_plain_mapping() {
+        let view   = INITIAL_VIEW;
+        let bounds = (120, 80);
+        let (width, height) = (view.lower_right.re - view.upper_left.re, view.upper_left.im - view.lower_right.im);
+        for y in 0 ..= bounds.1 {
+            for x in 0 ..= bounds.0 {
+                let plain = Complex { re: view.upper_left.re + x as f64 * width  / bounds.0 as f64,
+                                      im: view.upper_left.im - y as f64 * height / bounds.1 as f64 };
+                assert_eq!(pixel_to_point(bounds, (x, y), view.upper_left, view.lower_right, 1.0, 0.0), plain);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_colors_leave_top_byte_clear() {
+        // Stops past 0 ..= 255 would carry into the next channel up if color
+        // skipped its clamp; the lookup table only takes stops in range, so it
+        // gets the extremes.
+        let wild    = [(300.0, 512.0, 255.0), (255.0, 255.0, 255.0), (-20.0, 1000.0, 0.0)];
+        let palette = [(255.0, 255.0, 255.0), (0.0, 0.0, 0.0), (255.0, 0.0, 255.0)];
+        for step in 0 .. 4096 {
+            let iteration = step as f32 * 0.37;
+            assert_eq!(color(iteration, &wild) >> 24, 0);
+            assert_eq!(PaletteLut::new(&palette, 2.2).color(iteration) >> 24, 0);
+        }
+        let view         = View { upper_left:  Complex { re: -0.7445, im: 0.1320 },
+                                  lower_right: Complex { re: -0.7425, im: 0.1306 } };
+        let mut renderer = RendererBuilder::new().size(64, 48).limit(4096).supersample(2).palette(&palette).build();
+        let mut coloring = ColoringMode::Palette;
+        loop {
+            renderer.set_coloring(coloring);
+            let pixels = renderer.render(view);
+            assert!(pixels.iter().all(|&p| p >> 24 == 0), "{:?}", coloring);
+            coloring = coloring.next();
+            if coloring == ColoringMode::Palette {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn preview_uses_active_coloring() {
+        let view         = View { upper_left:  Complex { re: -0.7445, im: 0.1320 },
+                                  lower_right: Complex { re: -0.7421, im: 0.1304 } };
+        let mut renderer = RendererBuilder::new().size(96, 64).limit(256).build();
+        let mut coloring = ColoringMode::Palette;
+        loop {
+            renderer.set_coloring(coloring);
+            let full        = renderer.render(view);
+            let mut preview = vec![0; full.len()];
+            renderer.preview(view, view, 0.0, &mut preview);
+            // Previewing the cached view itself resamples every cell onto
+            // itself, so only the odd rounding in the blend may differ.
+            let same = full.iter().zip(preview.iter()).filter(|&(a, b)| a == b).count();
+            assert!(same * 100 >= full.len() * 99, "{:?}: {} of {} pixels match", coloring, same, full.len());
+            coloring = coloring.next();
+            if coloring == ColoringMode::Palette {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn only_perturbed_frames_mark_their_reference() {
+        let mut renderer = RendererBuilder::new().size(64, 48).limit(20000).limit_per_octave(0).tile_size(16)
+            .threads(2).build();
+        renderer.render(INITIAL_VIEW);
+        assert_eq!(renderer.reference(), None);
+        assert!(renderer.tile_timings().iter().all(|t| t.glitches == 0 && t.delta == 0.0));
+
+        let center = Complex { re: -0.743643887037151, im: 0.131825904205330 };
+        let half   = (2e-14, 1.5e-14);
+        renderer.render(View {
+            upper_left:  Complex { re: center.re - half.0, im: center.im + half.1 },
+            lower_right: Complex { re: center.re + half.0, im: center.im - half.1 },
+        });
+        let reference = renderer.reference().expect("deep view should perturb");
+        assert_eq!(reference.pixel, (32.0, 24.0));
+        assert!(reference.iterations > 0);
+        assert!(renderer.tile_timings().iter().any(|t| t.delta != 0.0));
+    }
+
+    #[test]
+    fn fixed_point_round_trips_f64() {
+        for &value in &[0.0, 1.0, -1.5, 0.1, -0.743643887037151, 3.0e-20, -2.5e7] {
+            assert_eq!(FixedPoint::new(value, 160).to_f64(), value);
+        }
+    }
+
+    #[test]
+    fn shallow_perturbation_matches_f64_kernel() {
+        let (width, height) = (64, 48);
+        let center          = Complex { re: -0.745, im: 0.11 };
+        let step            = 0.01 / width as f64;
+        let limit           = 500;
+        for &bits in &[0, 128, 256] {
+            let reference = ReferenceOrbit::new(center, FractalKind::Mandelbrot, limit, bits, SMOOTH_BAILOUT).unwrap();
+            let mut same  = 0;
+            for row in 0 .. height {
+                let offset        = (-(width as f64 / 2.0) * step, (height as f64 / 2.0 - row as f64) * step);
+                let mut direct    = vec![FullCell::default(); width];
+                let mut perturbed = vec![FullCell::default(); width];
+                render_row_deep(&mut direct, 0, (center.re + offset.0, center.im + offset.1), (step, 0.0), 0.0, limit,
+                                FractalKind::Mandelbrot, SMOOTH_BAILOUT);
+                render_row_perturbation(&mut perturbed, 0, offset, (step, 0.0), 0.0, limit, &reference);
+                // Both escape at the smooth coloring radius, so the fractions
+                // agree as well as the counts.
+                same += direct.iter().zip(&perturbed)
+                    .filter(|&(a, b)| a.count() == b.count() && (a.fraction() - b.fraction()).abs() < 1e-3).count();
+            }
+            assert!(same * 100 >= width * height * 99, "{} bits: {} of {} counts match", bits, same, width * height);
+        }
+    }
+
+    #[test]
+    fn precise_center_zooms_past_f64() {
+        // A few view widths off the Misiurewicz point i, much closer than f64
+        // resolves around 1.0.
+        let center  = PrecisePoint::parse("0.0000000000000000000000301234567890123",
+                                          "1.0000000000000000000000456789012345678").unwrap();
+        let (width, height, limit) = (16, 12, 1000);
+        let half    = 1e-24;
+        let offsets = View { upper_left:  Complex { re: -half, im:  half * 0.75 },
+                             lower_right: Complex { re:  half, im: -half * 0.75 } };
+        // Past f64 resolution the absolute corners collapse onto one row.
+        let placed  = center.to_f64();
+        assert_eq!((placed + offsets.upper_left).im, (placed + offsets.lower_right).im);
+        let values  = |center: &PrecisePoint| {
+            let mut renderer = RendererBuilder::new().size(width, height).limit(limit).limit_per_octave(0)
+                .coloring(ColoringMode::Palette).center(Some(center.clone())).build();
+            renderer.render(offsets);
+            renderer.cache().values()
+        };
+        let rendered = values(&center);
+        let exact    = ReferenceOrbit::precise(&center, FractalKind::Mandelbrot, limit, SMOOTH_BAILOUT).unwrap();
+        let matching = rendered.iter().enumerate().filter(|&(i, &value)| {
+            let offset = pixel_offset((width, height), (i % width, i / width), offsets.upper_left,
+                                      offsets.lower_right, 1.0, 0.0);
+            let (count, magnitude) = exact.iterate_exact(offset, limit);
+            let (count, fraction)  = smooth_count(count, magnitude, limit);
+            (count as f32 + fraction - value).abs() < 1e-3
+        }).count();
+        assert!(matching * 100 >= width * height * 99, "{} of {} values match", matching, width * height);
+        let (low, high) = rendered.iter().fold((f32::MAX, f32::MIN), |(l, h), &v| (l.min(v), h.max(v)));
+        assert!(high - low > 0.05, "the view has no detail");
+        // The same view around the center rounded to f64 lands elsewhere.
+        let rounded = values(&PrecisePoint::new(placed, center.bits()));
+        assert!(rounded.iter().zip(&rendered).filter(|&(a, b)| (a - b).abs() >= 1e-3).count() * 2 > width * height);
+    }
+
+    #[test]
+    fn precise_point_keeps_its_digits() {
+        let (re, im) = ("-0.74364388703715870475219150611477", "1.5e-30");
+        let point    = PrecisePoint::parse(re, im).unwrap();
+        assert_eq!(point.to_strings(), (re.to_string(), "0.0000000000000000000000000000015".to_string()));
+        assert_eq!(PrecisePoint::parse("0.25", "-2").unwrap().to_f64(), Complex { re: 0.25, im: -2.0 });
+        assert!(PrecisePoint::parse("1.2.3", "0").is_err());
+        assert!(PrecisePoint::parse("0", "e5").is_err());
+    }
+
+    #[test]
+    fn linear_colors_match_the_frame() {
+        // At gamma 1 no dark ramp is steepened by the encoding.
+        let mut renderer = RendererBuilder::new().size(64, 48).limit(256).gamma(1.0).build();
+        let frame        = renderer.render(INITIAL_VIEW);
+        let linear       = renderer.colorize_linear();
+        let values       = renderer.cache().values();
+        for (i, (&pixel, sample)) in frame.iter().zip(&linear).enumerate() {
+            // The frame's lookup table rounds the count down to a 64th of an
+            // iteration, which the steepest ramp turns into two levels.
+            for (c, shift) in [16, 8, 0].iter().enumerate() {
+                let encoded = ((pixel >> shift) & 0xFF) as i32;
+                assert!((linear_to_srgb(sample[c]) as i32 - encoded).abs() <= 3, "pixel {} channel {}", i, c);
+            }
+            assert_eq!(sample[3], values[i]);
+        }
+    }
+
+    #[test]
+    fn triangle_blends_what_box_keeps_apart() {
+        // Two white columns then two black ones, halved.
+        let src = vec![0x00FF_FFFF, 0x00FF_FFFF, 0, 0, 0x00FF_FFFF, 0x00FF_FFFF, 0, 0];
+        assert_eq!(downsample(&src, 2, 1, 2, DownsampleFilter::Box), vec![0x00FF_FFFF, 0]);
+        // The triangle takes an eighth of its weight from the other half.
+        let blended = downsample(&src, 2, 1, 2, DownsampleFilter::Triangle);
+        let level   = |p: u32| srgb_to_linear(p & 0xFF);
+        assert!((level(blended[0]) - 0.875).abs() < 0.01 && (level(blended[1]) - 0.125).abs() < 0.01,
+                "{:06x} {:06x}", blended[0], blended[1]);
+    }
+
+    #[test]
+    fn async_render_reports_a_panic_from_poll_and_join() {
+        let mut handle = RendererBuilder::new().size(32, 24).limit(64).build().render_async(INITIAL_VIEW);
+        let (_, pixels) = loop {
+            if handle.poll().unwrap().is_some() {
+                break handle.join().unwrap();
+            }
+            thread::yield_now();
+        };
+        assert_eq!(pixels.map(|p| p.len()), Some(32 * 24));
+        let (_, pixels) = RendererBuilder::new().size(0, 0).build().render_async(INITIAL_VIEW).join().unwrap();
+        assert_eq!(pixels.map(|p| p.len()), Some(0));
+
+        let mut handle = RendererBuilder::new().size(32, 24).build().spawn_render(|_, _| panic!("test render"));
+        let error      = loop {
+            match handle.poll() {
+                Ok(_)      => thread::yield_now(),
+                Err(error) => break error,
+            }
+        };
+        assert!(error.contains("panicked: test render"), "{}", error);
+        assert_eq!(handle.poll().err(), Some(error.clone()));
+        assert_eq!(handle.join().err(), Some(error));
+    }
+
+    #[test]
+    fn compact_cache_recolors_like_the_full_cache() {
+        let view    = View { upper_left:  Complex { re: -0.7485, im: 0.1352 },
+                             lower_right: Complex { re: -0.7385, im: 0.1277 } };
+        let palette = PaletteLut::new(COLORS, DEFAULT_GAMMA);
+        for &limit in &[256, 4096] {
+            let settings    = RenderSettings { limit, limit_per_octave: 0, ..RenderSettings::default() };
+            let mut full    = vec![FullCell::default(); 128 * 96];
+            let mut compact = vec![CompactCell::default(); 128 * 96];
+            let cancel      = CancelToken::new();
+            render_parallel(&mut full, (128, 96), view.upper_left, view.lower_right, &settings, (0.0, 0.0), &cancel);
+            render_parallel(&mut compact, (128, 96), view.upper_left, view.lower_right, &settings, (0.0, 0.0),
+                            &cancel);
+            let (mut expected, mut pixels) = (vec![0; 128 * 96], vec![0; 128 * 96]);
+            IterationCache::Compact(compact).colorize(&mut pixels, &palette, 0);
+            // Rounding the fraction to a 254th of an iteration can move a
+            // pixel to a neighboring lookup table entry, never further.
+            for (i, cell) in full.iter().enumerate() {
+                let value    = cell.count() as f32 + cell.fraction();
+                let mut near = [-1.0, 0.0, 1.0].iter().map(|&d| palette.color((value + d / LUT_STEPS as f32).max(0.0)));
+                assert!(near.any(|c| c == pixels[i]), "limit {} pixel {}: {:06x}", limit, i, pixels[i]);
+            }
+            IterationCache::Full(full).colorize(&mut expected, &palette, 0);
+            // Most pixels come out exactly the same.
+            let same = expected.iter().zip(&pixels).filter(|&(a, b)| a == b).count();
+            assert!(same * 10 >= pixels.len() * 9, "limit {}: only {} identical pixels", limit, same);
+        }
+    }
+
+    #[test]
+    fn deep_kernel_fractions_match_the_f32_kernel() {
+        let bailout = ColoringMode::Palette.bailout();
+        for &(x, y) in &[(-0.7435f32, 0.1314f32), (0.3, 0.5), (-1.25, 0.02), (0.26, 0.0)] {
+            let (count, magnitude) = mandelbrot_scalar(x, y, x, y, 1000, bailout);
+            let shallow            = smooth_count(count, magnitude, 1000);
+            let (count, magnitude) = mandelbrot_f64(x as f64, y as f64, x as f64, y as f64, 1000, bailout);
+            let deep               = smooth_count(count, magnitude, 1000);
+            assert_eq!(shallow.0, deep.0, "({}, {})", x, y);
+            assert!((shallow.1 - deep.1).abs() < 1e-3, "({}, {}): {} {}", x, y, shallow.1, deep.1);
+        }
+    }
+
+    fn bench_vector(b: &mut Bencher, c: (f32, f32)) {
+        let (x, y) = (f32x4::splat(c.0), f32x4::splat(c.1));
+        b.iter(|| mandelbrot_vector(black_box(x), black_box(y), x, y, 1000, ESCAPE_BAILOUT));
+    }
+
+    // Inside the period-3 bulb, past the cardioid test, so the periodicity
+    // check has to find the cycle.
+    #[bench]
+    fn bench_vector_interior(b: &mut Bencher) {
+        bench_vector(b, (-0.122, 0.745));
+    }
+
+    // Seahorse valley, which escapes only after hundreds of iterations.
+    #[bench]
+    fn bench_vector_boundary(b: &mut Bencher) {
+        bench_vector(b, (-0.7435, 0.1314));
+    }
+
+    #[bench]
+    fn bench_vector_exterior(b: &mut Bencher) {
+        bench_vector(b, (1.0, 1.0));
+    }
+
+    // A 16 row band through the middle of the initial view.
+    #[bench]
+    fn bench_render_band(b: &mut Bencher) {
+        let band = View {
+            upper_left:  Complex { re: INITIAL_VIEW.upper_left.re,  im:  0.05 },
+            lower_right: Complex { re: INITIAL_VIEW.lower_right.re, im: -0.05 },
+        };
+        let settings = RenderSettings { limit: 256, kernel: KernelWidth::X4, ..RenderSettings::default() };
+        b.iter(|| render_cells((256, 16), black_box(band), &settings));
+    }
+
+    // The initial view at 256x192, iterated once, so the coloring benches
+    // time only the color stage.
+    fn colored_cache() -> IterationCache {
+        let settings = RenderSettings { limit: 256, ..RenderSettings::default() };
+        IterationCache::Full(render_cells((256, 192), INITIAL_VIEW, &settings))
+    }
+
+    #[bench]
+    fn bench_color_lut(b: &mut Bencher) {
+        let cache      = colored_cache();
+        let palette    = PaletteLut::new(COLORS, 1.0);
+        let mut pixels = vec![0; 256 * 192];
+        b.iter(|| cache.colorize(black_box(&mut pixels), &palette, 0));
+    }
+
+    // The per-pixel interpolation the lookup table replaced.
+    #[bench]
+    fn bench_color_interpolated(b: &mut Bencher) {
+        let cache      = colored_cache();
+        let mut pixels = vec![0; 256 * 192];
+        let cells      = match cache {
+            IterationCache::Full(ref cells) => cells,
+            IterationCache::Compact(_)      => unreachable!(),
+        };
+        b.iter(|| for (pixel, cell) in black_box(&mut pixels).iter_mut().zip(cells.iter()) {
+            *pixel = color(cell.count() as f32 + cell.fraction(), COLORS);
+        });
+    }
+
+    // A whole frame, iterated and colorized, in `coloring`.
+    fn bench_frame(b: &mut Bencher, coloring: ColoringMode) {
+        let mut renderer = RendererBuilder::new().size(256, 192).limit(256).threads(1).coloring(coloring).build();
+        let mut pixels   = vec![0; 256 * 192];
+        let cancel       = CancelToken::new();
+        b.iter(|| {
+            renderer.iterate(black_box(INITIAL_VIEW), (0.0, 0.0), &cancel);
+            renderer.colorize(&mut pixels)
+        });
+    }
+
+    #[bench]
+    fn bench_frame_raw(b: &mut Bencher) {
+        bench_frame(b, ColoringMode::Raw);
+    }
+
+    #[bench]
+    fn bench_frame_palette(b: &mut Bencher) {
+        bench_frame(b, ColoringMode::Palette);
+    }
+}
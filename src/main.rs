@@ -1,365 +1,524 @@
-#![feature(iterator_step_by, test)]
+#![recursion_limit = "1024"]
+// The same lint exceptions as lib.rs, for the same reasons.
+#![allow(clippy::redundant_static_lifetimes, clippy::neg_cmp_op_on_partial_ord, clippy::manual_clamp,
+         clippy::too_many_arguments, clippy::needless_range_loop)]
 
 #[macro_use]
 extern crate cpp;
 #[macro_use]
 extern crate lazy_static;
 
-cpp!{{
-    #include <stdio.h>
-    #include "imgui/imgui.h"
-}}
-
-
 extern crate num;
 extern crate minifb;
-extern crate crossbeam;
-extern crate simd;
+// Only linked for the imgui it compiles; the cpp! blocks call it directly.
+extern crate imgui_sys;
 extern crate libc;
-
-use minifb::{Key, WindowOptions, Window};
+extern crate exr;
+extern crate png;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate mandelbrot;
+
+mod batch;
+mod cli;
+mod export;
+mod navigation;
+mod overlay;
+mod raster;
+mod render_thread;
+mod state;
+mod ui;
+
+use batch::run_batch_mode;
+use cli::{config_from_args, flag_value, formula_from_args, palettes_from_args, parse_pair, ui_scale};
+use export::{CONTOUR_LEVELS, export_view_exr, export_view_svg, save_screenshot, screenshot_path, write_ppm};
+use navigation::{KEY_ZOOM, ROTATION_STEP, WHEEL_ZOOM, adjust_limit, home_view, julia_step, pan_step, resize_view,
+                 selection_view, zoom_view};
+use overlay::{draw_crosshair, draw_heatmap, draw_reference, draw_selection};
+use raster::BlendMode;
+use render_thread::{FrameStats, GlobalBuffer, GlobalReference, GlobalTiles, Refinement, RenderJob, RenderQueue,
+                    grid_jobs, spawn_render_thread};
+use state::{Bookmark, ViewState, BOOKMARKS_PATH, BOOKMARK_KEYS, VIEW_STATE_PATH, load_bookmarks, load_view_state,
+            save_bookmarks, save_view_state};
+use ui::{FrameTimer, GlobalUi, Stats, composite_ui, draw_index_size, font_atlas_size, init_imgui, invalidate_ui_cache,
+         render_stats, shutdown_imgui};
+use mandelbrot::{ColoringMode, Config, FractalKind, KernelWidth, RendererBuilder, TileTuner, View, DEFAULT_JITTER_SEED,
+                 DEFAULT_LIMIT_PER_OCTAVE};
+use mandelbrot::{fit_bounds_to_aspect, next_supersample, pixel_to_point, view_center};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, WindowOptions, Window};
 use num::Complex;
-use simd::{f32x4, u32x4};
-use std::sync::Mutex;
-
-const WIDTH:       usize = 1024;
-const HEIGHT:      usize = 768;
-const LIMIT:       u32   = 100;
-const NUM_THREADS: usize = 4;
-const COLORS: &'static [(f32, f32, f32)] = &[(0.0,    7.0,    100.0),
-                                             (32.0,   107.0,  203.0),
-                                             (237.0,  255.0,  255.0),
-                                             (255.0,  170.0,  0.0),
-                                             (0.0,    2.0,    0.0)];
-
-#[repr(C)]
-struct Point2D {
-    x: i32,
-    y: i32
+use std::env;
+use std::error::Error;
+use std::f64;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Sizes the UI layer to the window. The frame buffer is left to the render
+// thread, which presents at the new size once it has rendered it; until then
+// the last frame stays up.
+fn allocate_ui_layer(config: &Config) {
+    let mut layer = GlobalUi.lock().unwrap();
+    layer.pixels  = vec![0; config.width * config.height];
+    layer.width   = config.width;
+    layer.hash    = None;
 }
 
-#[repr(C)]
-struct Point2DF {
-    x: f32,
-    y: f32,
+// Grid mode posts the job's cells as one batch instead.
+fn post_job(queue: &RenderQueue<RenderJob>, job: RenderJob, grid: bool) {
+    if grid { queue.post_batch(grid_jobs(job)) } else { queue.post(job) }
 }
 
-lazy_static! {
-    static ref GlobalBuffer: Mutex<Vec<u32>> = Mutex::new(vec![0; WIDTH * HEIGHT]);
+// The last presented frame, unless the render thread has not caught up with a
+// resize yet.
+fn current_frame(config: &Config) -> Option<Vec<u32>> {
+    let front = GlobalBuffer.lock().unwrap();
+    if front.len() == config.width * config.height { Some(front.clone()) } else { None }
 }
 
-fn pixel_to_point(bounds:      (usize, usize),
-                  pixel:       (usize, usize),
-                  upper_left:  Complex<f64>,
-                  lower_right: Complex<f64>) -> Complex<f64> {
+// minifb can't query the monitor, so fullscreen takes its size from --screen.
+const DEFAULT_SCREEN_SIZE: (usize, usize) = (1920, 1080);
 
-    let (width, height) = (lower_right.re - upper_left.re,
-                           upper_left.im  - lower_right.im);
-    Complex {
-        re: upper_left.re + pixel.0 as f64 * width  / bounds.0 as f64,
-        im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64
+// A fullscreen window is a borderless, fixed-size one covering the screen.
+fn open_window(size: (usize, usize), fullscreen: bool) -> minifb::Result<Window> {
+    let options    = WindowOptions { borderless: fullscreen, title: !fullscreen, resize: !fullscreen,
+                                     ..WindowOptions::default() };
+    let mut window = Window::new("Sample RGBA32 buffer", size.0, size.1, options)?;
+    if fullscreen {
+        window.set_position(0, 0);
     }
+    Ok(window)
 }
 
-#[inline(never)]
-fn mandelbrot_vector(c_x: f32x4, c_y: f32x4, max_iter: u32) -> u32x4 {
-    let mut x = c_x;
-    let mut y = c_y;
-    let mut count = u32x4::splat(0);
-    for _ in 0..max_iter as usize {
-        let xy = x * y;
-        let xx = x * x;
-        let yy = y * y;
-        let sum = xx + yy;
-        let mask = sum.lt(f32x4::splat(4.0));
-        if !mask.any() { break }
-        count = count + mask.to_i().select(u32x4::splat(1), u32x4::splat(0));
-        x = xx - yy + c_x;
-        y = xy + xy + c_y;
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    let mut config        = config_from_args(&args);
+    if run_batch_mode(&args, &config) {
+        return Ok(());
     }
-    count
-}
+    allocate_ui_layer(&config);
 
-#[inline(never)]
-fn render(pixels:      &mut [u32],
-          bounds:      (usize, usize),
-          upper_left:  Complex<f64>,
-          lower_right: Complex<f64>) {
+    // Batch subcommands returned above without touching the display.
+    let mut window = open_window((config.width, config.height), false).map_err(|e| {
+        println!("Could not open a window: {}", e);
+        println!("Without a display, render headless with --headless, --emit-manifest/--render-tile, --export-svg, --export-frames or --export-pyramid.");
+        e
+    })?;
 
-    assert!(pixels.len() == bounds.0 * bounds.1);
-
-    let left             = upper_left.re  as f32;
-    let right            = lower_right.re as f32;
-    let top              = upper_left.im  as f32;
-    let bottom           = lower_right.im as f32;
-    let width_step:  f32 = (right - left) / WIDTH as f32;
-    let height_step: f32 = (bottom - top) / (HEIGHT as f32 / NUM_THREADS as f32) ;
-    let adjust           = f32x4::splat(width_step) * f32x4::new(0., 1., 2., 3.);
-
-    for row in 0 .. bounds.1 {
-        let y = f32x4::splat(top + height_step * row as f32);
-        for column in (0 .. bounds.0).step_by(4) {
-            let x = f32x4::splat(left + width_step * column as f32) + adjust;
-            let points = mandelbrot_vector(x, y, LIMIT);            
-            for k in 0..4 {
-                let (r, g, b);
-                let point_k = points.extract(k as u32);
-                let val   = (point_k as f32 % 12.0) * (COLORS.len() as f32) / 12.0;
-                let left  = val as usize % COLORS.len();
-                let right = (left + 1) % COLORS.len();
-
-                let p = val - left as f32;
-                let (r1, g1, b1) = COLORS[left];
-                let (r2, g2, b2) = COLORS[right];
-                r = (r1 + (r2 - r1) * p) as u32;
-                g = (g1 + (g2 - g1) * p) as u32;
-                b = (b1 + (b2 - b1) * p) as u32;
-                pixels[row * bounds.0 + column + k] = (r << 16) + (g << 8) + b
-            }            
-        }
+    println!("Renderer version: 0.0.666, {}, {}", env::consts::ARCH, KernelWidth::fastest().isa());
+    println!("========================================");
+    println!("Running with {} threads.", config.num_threads);
+    println!("Buffer resolution: {} - {}.", config.width, config.height);
+    
+    init_imgui(&config, ui_scale(&args));
+    if flag_value(&args, "--ui-blend") == Some("premultiplied") {
+        GlobalUi.lock().unwrap().blend = BlendMode::Premultiplied;
     }
-}
-
-fn render_parallel(bounds:      (usize, usize),
-                   upper_left:  Complex<f64>,
-                   lower_right: Complex<f64>) {
-    let rows_per_band = bounds.1 / NUM_THREADS + 1;
-    let mut buffer_contents = GlobalBuffer.lock().unwrap();
-    let bands: Vec<&mut [u32]> = buffer_contents.chunks_mut(rows_per_band * bounds.0).collect();
-    crossbeam::scope(|spawner| {
-        for (i, band) in bands.into_iter().enumerate() {
-            let top              = rows_per_band * i;
-            let height           = band.len() / bounds.0;
-            let band_bounds      = (bounds.0, height);
-            let band_upper_left  = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-            let band_lower_right = pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
-            spawner.spawn(move || {
-                render(band, band_bounds, band_upper_left, band_lower_right);
-            });
-        }
-    });    
-}
-
-#[inline]
-fn edge_function(p0: &Point2DF, p1: &Point2DF, p2: &Point2DF) -> f32 {
-    (p1.x - p0.x) * (p2.y - p0.y) - (p1.y - p0.y) * (p2.x - p0.x)
-}
-
-#[inline]
-fn min3(x: f32, y: f32, z: f32) -> f32 {
-    let mut min = x;
-    if y < min { min = y; }
-    if z < min { min = z; }
-    min as f32
-}
-
-#[inline]
-fn max3(x: f32, y: f32, z: f32) -> f32 {
-    let mut max = x;
-    if y > max { max = y; }
-    if z > max { max = z; }
-    max as f32
-}
-
-fn draw_triangle(p0: &Point2DF, p1: &Point2DF, p2: &Point2DF,
-                 R0: f32, G0: f32, B0: f32, A0: f32,
-                 R1: f32, G1: f32, B1: f32, A1: f32,
-                 R2: f32, G2: f32, B2: f32, A2: f32,
-                 uv0: &Point2DF, uv1: &Point2DF, uv2: &Point2DF) {
-    let area = edge_function(&p0, &p1, &p2);
-    let min_x = min3(p0.x, p1.x, p2.x);
-    let max_x = max3(p0.x, p1.x, p2.x);
-    let min_y = min3(p0.y, p1.y, p2.y);
-    let max_y = max3(p0.y, p1.y, p2.y);
-
-    let mut p_y = min_y.ceil() as i32;
-    let mut p_x = min_x.ceil() as i32;
-    for y in min_y.ceil() as i32 .. max_y.ceil() as i32 {
-        for x in min_x.ceil() as i32 .. max_x.ceil() as i32 {
-            let p = Point2DF {x: x as f32, y: y as f32};
-            let mut w0 = edge_function(&p1, &p2, &p);
-            let mut w1 = edge_function(&p2, &p0, &p);
-            let mut w2 = edge_function(&p0, &p1, &p);
-
-            if (w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0) {
-                w0 /= area;
-                w1 /= area;
-                w2 /= area;
-
-                let MeshR = ((w0 * R0 + w1 * R1 + w2 * R2) * 255.0) as u32;
-                let MeshG = ((w0 * G0 + w1 * G1 + w2 * G2) * 255.0) as u32;
-                let MeshB = ((w0 * B0 + w1 * B1 + w2 * B2) * 255.0) as u32;
-                let MeshA = ((w0 * A0 + w1 * A1 + w2 * A2) * 255.0) as u32;
-                let mut pixels = GlobalBuffer.lock().unwrap();
-                let pixel_index = y as usize * WIDTH + x as usize;
-
-                let background_color = pixels[pixel_index];
-
-                pixels[pixel_index] = (MeshR << 16) + (MeshG << 8) + MeshB;
-            }
+    let (atlas_w, atlas_h) = font_atlas_size();
+    println!("Font atlas: {} - {}.", atlas_w, atlas_h);
+    println!("imgui index size: {} bits.", draw_index_size() * 8);
+
+    let mut fractal     = FractalKind::Mandelbrot;
+    let initial         = home_view(fractal, &config);
+    let mut upper_left  = initial.upper_left;
+    let mut lower_right = initial.lower_right;
+    let mut kernel      = KernelWidth::fastest();
+    let mut coloring    = ColoringMode::Palette;
+    let mut palette     = 0;
+    let mut compare     = None;
+    let mut bookmarks   = load_bookmarks(BOOKMARKS_PATH);
+    let mut crosshair   = false;
+    let mut heatmap     = false;
+    let mut references  = false;
+    let mut grid        = false;
+    // The windowed size to go back to while fullscreen.
+    let mut windowed    = None;
+    let screen_size     = flag_value(&args, "--screen").and_then(parse_pair).unwrap_or(DEFAULT_SCREEN_SIZE);
+    // Window corners of the zoom box while Shift-dragging.
+    let mut selection   = None;
+    // Button and cursor position of the previous frame while dragging.
+    let mut drag_from   = None;
+    let home_rotation   = flag_value(&args, "--rotate").and_then(|d| d.parse::<f64>().ok())
+        .unwrap_or(0.0).to_radians();
+    let mut rotation    = home_rotation;
+    let palettes        = Arc::new(palettes_from_args(&args));
+    if let Some(path) = flag_value(&args, "--load") {
+        let mut view   = View { upper_left, lower_right };
+        let mut center = None;
+        load_view_state(path)?.apply(&mut view, &mut center, &mut rotation, &mut config.limit, &mut fractal,
+                                     &mut palette, &mut coloring, palettes.len())?;
+        // The viewer navigates in f64, so a deep state opens at the f64
+        // rounding of its center; --headless renders it exactly.
+        if let Some(center) = center {
+            let placed = center.to_f64();
+            view       = View { upper_left: view.upper_left + placed, lower_right: view.lower_right + placed };
         }
+        let fitted     = fit_bounds_to_aspect(view.upper_left, view.lower_right, config.width, config.height);
+        upper_left     = fitted.upper_left;
+        lower_right    = fitted.lower_right;
     }
-}
-
-fn fetch_render_data(_im_draw_data: *const ()) {
-    let rasterizer = draw_triangle as *const ();
-    unsafe {
-        cpp!([_im_draw_data as "void *", rasterizer as "void *"] {             
-            
-            struct Point2DF {
-                float X;
-                float Y;
-            };
-
-            typedef void DrawTriangle(Point2DF* p0, Point2DF* p1, Point2DF* p2,
-                                      float R0, float G0, float B0, float A0,
-                                      float R1, float G1, float B1, float A1,
-                                      float R2, float G2, float B2, float A2,
-                                      Point2DF* uv0, Point2DF* uv1, Point2DF* uv2);      
-            DrawTriangle *rusterizer = (DrawTriangle *) rasterizer;
-            ImGuiIO& io = ImGui::GetIO();
-            int fb_width  = (int)(io.DisplaySize.x * io.DisplayFramebufferScale.x);
-            int fb_height = (int)(io.DisplaySize.y * io.DisplayFramebufferScale.y);
-            if (fb_width == 0 || fb_height == 0) {
-                printf("Skip frame\n");
-                return;
+    let stats           = Arc::new(Mutex::new(FrameStats::default()));
+    // Each render thread owns a full renderer, so more than one only pays off
+    // for independent views like the grid's cells; one thread with a
+    // coalescing queue is the default.
+    let max_renders     = flag_value(&args, "--max-renders").and_then(|n| n.parse().ok()).unwrap_or(1);
+    let queue           = Arc::new(RenderQueue::new(max_renders));
+    // --tile-size takes a tile edge in pixels, or `auto` to let TileTuner pick one.
+    let tile_size   = flag_value(&args, "--tile-size");
+    let auto_tile   = tile_size == Some("auto");
+    let progressive = args.iter().any(|a| a == "--progressive");
+    let preview     = args.iter().any(|a| a == "--zoom-preview");
+    let coarse      = args.iter().any(|a| a == "--coarse-preview");
+    let builder     = RendererBuilder::new()
+        .config(&config)
+        .threads(queue.worker_threads(config.num_threads))
+        .supersample(config.supersample)
+        .palette(&palettes[0].1)
+        .formula(formula_from_args(&args))
+        .limit_per_octave(flag_value(&args, "--limit-per-octave").and_then(|k| k.parse().ok())
+                              .unwrap_or(DEFAULT_LIMIT_PER_OCTAVE))
+        .jitter_seed(flag_value(&args, "--jitter-seed").and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_JITTER_SEED))
+        .tile_size(tile_size.and_then(|t| t.parse().ok()).unwrap_or(0));
+    for _ in 0 .. queue.concurrency() {
+        let tuner      = if auto_tile { Some(TileTuner::new()) } else { None };
+        let refinement = if progressive { Some(Refinement::new(config.limit)) } else { None };
+        spawn_render_thread(queue.clone(), stats.clone(), builder.clone(), palettes.clone(), tuner, refinement,
+                            preview, coarse);
+    }
+    post_job(&queue, RenderJob { view: View { upper_left, lower_right }, kernel, coloring, palette, rotation, fractal,
+                                 size: (config.width, config.height), supersample: config.supersample,
+                                 limit: config.limit, compare, cell: None }, grid);
+
+    // The UI ticks once per display frame; the fractal arrives from the render
+    // thread whenever it finishes, so a slow render never stalls imgui.
+    let mut last_frame = Instant::now();
+    let mut timer      = FrameTimer::new();
+    let mut screen     = Vec::new();
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let mut need_update = false;
+        let now            = Instant::now();
+        let delta_time     = now.duration_since(last_frame).as_secs_f32();
+        last_frame         = now;
+        let window_size    = window.get_size();
+        if window_size != (config.width, config.height) && window_size.0 > 0 && window_size.1 > 0 {
+            let resized   = resize_view(View { upper_left, lower_right }, (config.width, config.height), window_size);
+            let fitted    = fit_bounds_to_aspect(resized.upper_left, resized.lower_right,
+                                                 window_size.0, window_size.1);
+            upper_left    = fitted.upper_left;
+            lower_right   = fitted.lower_right;
+            config.width  = window_size.0;
+            config.height = window_size.1;
+            allocate_ui_layer(&config);
+            need_update   = true;
+        }
+        timer.record(delta_time * 1000.0);
+        // Read every frame, so the coordinates follow the cursor between renders.
+        let cursor         = window.get_mouse_pos(MouseMode::Discard).map(|(x, y)| {
+            pixel_to_point((config.width, config.height), (x as usize, y as usize), upper_left, lower_right,
+                           1.0, rotation)
+        });
+        let overlay        = Stats::new(kernel, coloring, &palettes[palette].0, fractal,
+                                        View { upper_left, lower_right }, cursor, *stats.lock().unwrap(), &config,
+                                        timer.shown_ms());
+        render_stats(&overlay, &config, delta_time);
+        if window.is_key_pressed(Key::V, KeyRepeat::No) {
+            kernel      = kernel.next();
+            need_update = true;
+        }
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            palette     = (palette + 1) % palettes.len();
+            need_update = true;
+        }
+        if window.is_key_pressed(Key::U, KeyRepeat::No) {
+            config.supersample = next_supersample(config.supersample);
+            need_update        = true;
+        }
+        if window.is_key_pressed(Key::LeftBracket, KeyRepeat::Yes) {
+            config.limit = adjust_limit(config.limit, false);
+            need_update  = true;
+        }
+        if window.is_key_pressed(Key::RightBracket, KeyRepeat::Yes) {
+            config.limit = adjust_limit(config.limit, true);
+            need_update  = true;
+        }
+        if window.is_key_pressed(Key::X, KeyRepeat::No) {
+            crosshair = !crosshair;
+        }
+        // Debug overlay of what each tile of the last frame cost.
+        if window.is_key_pressed(Key::F3, KeyRepeat::No) {
+            heatmap = !heatmap;
+        }
+        if window.is_key_pressed(Key::F4, KeyRepeat::No) {
+            references = !references;
+        }
+        // The current view at several depths, one render job per cell.
+        if window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            grid        = !grid;
+            need_update = true;
+        }
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            coloring    = coloring.next();
+            need_update = true;
+        }
+        if window.is_key_pressed(Key::H, KeyRepeat::No) {
+            coloring    = if coloring == ColoringMode::Histogram { ColoringMode::Palette } else { ColoringMode::Histogram };
+            need_update = true;
+        }
+        if window.is_key_pressed(Key::Comma, KeyRepeat::Yes) {
+            rotation   += ROTATION_STEP;
+            need_update = true;
+        }
+        if window.is_key_pressed(Key::Period, KeyRepeat::Yes) {
+            rotation   -= ROTATION_STEP;
+            need_update = true;
+        }
+        // J cycles Mandelbrot, Julia and Burning Ship, each starting from its
+        // own view.
+        if window.is_key_pressed(Key::J, KeyRepeat::No) {
+            fractal     = fractal.next();
+            let home    = home_view(fractal, &config);
+            upper_left  = home.upper_left;
+            lower_right = home.lower_right;
+            need_update = true;
+        }
+        // A number key stores the view in its slot, Shift and the number jumps
+        // back to it. Jumping to an empty slot does nothing.
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        for (slot, &key) in BOOKMARK_KEYS.iter().enumerate() {
+            if !window.is_key_pressed(key, KeyRepeat::No) {
+                continue;
             }
-            ImDrawData *data = (ImDrawData *)_im_draw_data;
-            data->ScaleClipRects(io.DisplayFramebufferScale);
-            for (int n = 0; n < data->CmdListsCount; n++) {
-                const ImDrawList *cmd_list = data->CmdLists[n];
-                unsigned int IndexOffset = 0;
-                for (int cmd_i = 0; cmd_i < cmd_list->CmdBuffer.Size; cmd_i++) {
-                    const ImDrawCmd *pcmd = &cmd_list->CmdBuffer[cmd_i];
-                    unsigned int ElementCount = (unsigned int)pcmd->ElemCount;
-                    if (pcmd->UserCallback) {
-                        printf("User input is not implemented.\n");
-                    } else {
-                        for (unsigned int i = 0; i < ElementCount; i+= 3) {
-                            unsigned int idx0 = cmd_list->IdxBuffer[IndexOffset + i];
-                            unsigned int idx1 = cmd_list->IdxBuffer[IndexOffset + i + 1];
-                            unsigned int idx2 = cmd_list->IdxBuffer[IndexOffset + i + 2];
-
-                            Point2DF p0  = {cmd_list->VtxBuffer[idx0].pos.x,
-                                            cmd_list->VtxBuffer[idx0].pos.y};
-                            Point2DF p1  = {cmd_list->VtxBuffer[idx1].pos.x,
-                                            cmd_list->VtxBuffer[idx1].pos.y};
-                            Point2DF p2  = {cmd_list->VtxBuffer[idx2].pos.x,
-                                            cmd_list->VtxBuffer[idx2].pos.y};
-
-                            Point2DF uv0 = {cmd_list->VtxBuffer[idx0].uv.x,
-                                            cmd_list->VtxBuffer[idx0].uv.y};
-                            Point2DF uv1 = {cmd_list->VtxBuffer[idx1].uv.x,
-                                            cmd_list->VtxBuffer[idx1].uv.y};
-                            Point2DF uv2 = {cmd_list->VtxBuffer[idx2].uv.x,
-                                            cmd_list->VtxBuffer[idx2].uv.y};
-                            
-                            ImVec4 rgba0 = ImGui::ColorConvertU32ToFloat4(cmd_list->VtxBuffer[idx0].col);
-                            ImVec4 rgba1 = ImGui::ColorConvertU32ToFloat4(cmd_list->VtxBuffer[idx1].col);
-                            ImVec4 rgba2 = ImGui::ColorConvertU32ToFloat4(cmd_list->VtxBuffer[idx2].col);
-                            
-                            rusterizer(&p0, &p1, &p2,
-                                       rgba0.x, rgba0.y, rgba0.z, rgba0.w,
-                                       rgba1.x, rgba1.y, rgba1.z, rgba1.w,
-                                       rgba2.x, rgba2.y, rgba2.z, rgba2.w,
-                                       &uv0, &uv1, &uv2);
+            if shift {
+                if let Some(ref bookmark) = bookmarks[slot] {
+                    match bookmark.fractal() {
+                        Ok(kind) => {
+                            let saved    = bookmark.view();
+                            let view     = fit_bounds_to_aspect(saved.upper_left, saved.lower_right,
+                                                                config.width, config.height);
+                            upper_left   = view.upper_left;
+                            lower_right  = view.lower_right;
+                            rotation     = bookmark.rotation();
+                            config.limit = bookmark.limit();
+                            fractal      = kind;
+                            need_update  = true;
                         }
+                        Err(e)   => println!("Ignoring bookmark {}: {}", slot + 1, e),
                     }
-                    IndexOffset += ElementCount;
                 }
-            }
-        });
-    }    
-}
-
-fn init_imgui() {
-    unsafe {
-        let w = WIDTH  as u32;
-        let h = HEIGHT as u32;
-        let renderer = fetch_render_data as *const ();
-        cpp!([w as "int32_t", h as "int32_t", renderer as "void *"] {
-            typedef void rust_renderer(ImDrawData *data);
-            printf("Starting imgui initialization...\n");
-            ImGui::CreateContext();
-            ImGuiIO& io = ImGui::GetIO();
-            io.RenderDrawListsFn = (rust_renderer*)renderer;
-            io.DisplaySize = ImVec2((float)w, (float)h);               
-            unsigned char *font_texture = NULL;
-            int tex_w, tex_h, tex_bpp;
-            io.Fonts->GetTexDataAsAlpha8(&font_texture, &tex_w, &tex_h, &tex_bpp);
-            printf("OK: Finishing imgui initialization.\n");
-        });
-    }
-}
-
-fn render_stats() {
-    let w = WIDTH  as u32;
-    let h = HEIGHT as u32;
-    unsafe {   
-        cpp!([w as "int32_t", h as "int32_t"] {
-            ImGuiIO& io = ImGui::GetIO();
-            io.DisplaySize = ImVec2(w, h); 
-            ImGui::NewFrame();
-            ImGui::Begin("Stats", 0);
-            ImGui::SetWindowPos("Stats", ImVec2(10, 10));
-            ImGui::SetWindowSize(ImVec2(300, 85));
-            
-            ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(1.0f, 0.2f, 0.2f, 1.0f));
-            ImGui::Text("Milliseconds per frame: ");
-            ImGui::PopStyleColor();
-
-            ImGui::End();
-            ImGui::Render();
-            ImGui::EndFrame();
-        });
-    }
-}
-
-fn main() {
-    let mut window = Window::new(
-        "Sample RGBA32 buffer", WIDTH, HEIGHT, WindowOptions::default()
-    ).unwrap_or_else(|e| { panic!("{}", e); });       
-
-    println!("Renderer version: 0.0.666, x86_64, AVX2");
-    println!("========================================");
-    println!("Running with {} threads.", NUM_THREADS);
-    println!("Buffer resolution: {} - {}.", WIDTH, HEIGHT);
-    
-    init_imgui();
-
-    let mut upper_left  = Complex {re: -2.2, im:  1.0};
-    let mut lower_right = Complex {re:  1.2, im: -1.0};
-    let mut step        = 0.01;
-    render_parallel((WIDTH, HEIGHT), upper_left, lower_right);
-
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let mut need_update = false;
-        render_stats();
-        window.get_keys().map(|keys| {            
-            for k in keys {
-                match k {
-                    Key::Left  => {upper_left.re -= step; lower_right.re -= step;},
-                    Key::A     => {upper_left.re -= step; lower_right.re -= step;},
-                    Key::Right => {upper_left.re += step; lower_right.re += step;},
-                    Key::D     => {upper_left.re += step; lower_right.re += step;},
-                    Key::Up    => {upper_left.im += step; lower_right.im += step;},
-                    Key::Down  => {upper_left.im -= step; lower_right.im -= step;},
-                    Key::W     => {upper_left.re /= 1.01; lower_right.re /= 1.01;
-                                   upper_left.im /= 1.01; lower_right.im /= 1.01;
-                                   step          /= 1.01;},
-                    Key::S     => {upper_left.re *= 1.01; lower_right.re *= 1.01;
-                                   upper_left.im *= 1.01; lower_right.im *= 1.01;
-                                   step          *= 1.01},
-                    _          => (),
+            } else {
+                bookmarks[slot] = Some(Bookmark::capture(View { upper_left, lower_right }, rotation, config.limit,
+                                                         fractal));
+                match save_bookmarks(BOOKMARKS_PATH, &bookmarks) {
+                    Ok(()) => println!("Saved bookmark {} to {}.", slot + 1, BOOKMARKS_PATH),
+                    Err(e) => println!("Failed to save {}: {}", BOOKMARKS_PATH, e),
                 }
+            }
+        }
+        if window.is_key_pressed(Key::K, KeyRepeat::No) {
+            let state = ViewState::capture(View { upper_left, lower_right }, None, rotation, config.limit, fractal,
+                                           palette, coloring);
+            match save_view_state(VIEW_STATE_PATH, &state) {
+                Ok(()) => println!("Saved {}.", VIEW_STATE_PATH),
+                Err(e) => println!("Failed to save {}: {}", VIEW_STATE_PATH, e),
+            }
+        }
+        if window.is_key_pressed(Key::Home, KeyRepeat::No) {
+            let home    = home_view(fractal, &config);
+            upper_left  = home.upper_left;
+            lower_right = home.lower_right;
+            rotation    = home_rotation;
+            need_update = true;
+        }
+        // Shift and a left drag frame a box instead of panning; releasing
+        // either zooms to it.
+        if shift && window.get_mouse_down(MouseButton::Left) {
+            if let Some(pos) = window.get_mouse_pos(MouseMode::Clamp) {
+                selection = Some((selection.map(|(start, _)| start).unwrap_or(pos), pos));
+            }
+        } else if let Some((start, end)) = selection.take() {
+            if let Some(view) = selection_view(View { upper_left, lower_right }, rotation,
+                                               (config.width, config.height), start, end) {
+                upper_left  = view.upper_left;
+                lower_right = view.lower_right;
                 need_update = true;
             }
-            if need_update {
-                render_parallel((WIDTH, HEIGHT), upper_left, lower_right);
-                render_stats();
-                need_update = false;                
+        }
+        // Left drag pans so the plane follows the cursor, right drag moves the
+        // Julia constant by the dragged distance. Only frames with the button
+        // still down pair up, so a fresh press never jumps by the distance the
+        // cursor travelled while released.
+        let button = if selection.is_some() {
+            None
+        } else if window.get_mouse_down(MouseButton::Left) {
+            Some(MouseButton::Left)
+        } else if window.get_mouse_down(MouseButton::Right) {
+            Some(MouseButton::Right)
+        } else {
+            None
+        };
+        let mouse = button.zip(window.get_mouse_pos(MouseMode::Discard));
+        if let (Some((button, (x, y))), Some((from_button, (from_x, from_y)))) = (mouse, drag_from) {
+            if button == from_button && (x != from_x || y != from_y) {
+                let (sin, cos) = rotation.sin_cos();
+                let dx         = (x - from_x) as f64 * (lower_right.re - upper_left.re) / config.width  as f64;
+                let dy         = (from_y - y) as f64 * (upper_left.im - lower_right.im) / config.height as f64;
+                let delta      = (dx * cos - dy * sin, dx * sin + dy * cos);
+                match button {
+                    MouseButton::Left => {
+                        upper_left  = Complex { re: upper_left.re  - delta.0, im: upper_left.im  - delta.1 };
+                        lower_right = Complex { re: lower_right.re - delta.0, im: lower_right.im - delta.1 };
+                        need_update = true;
+                    },
+                    _ => if let FractalKind::Julia { ref mut c } = fractal {
+                        *c          = Complex { re: c.re + delta.0, im: c.im + delta.1 };
+                        need_update = true;
+                    },
+                }
             }
-        });
-        window.update_with_buffer(&GlobalBuffer.lock().unwrap()).unwrap();
+        }
+        drag_from = mouse;
+        // Diff the active kernel against the scalar reference.
+        if window.is_key_pressed(Key::F, KeyRepeat::No) {
+            compare     = if compare.is_some() { None } else { Some(KernelWidth::Scalar) };
+            need_update = true;
+        }
+        // The new window's size goes through the resize above on the next
+        // frame, which keeps the view center.
+        if window.is_key_pressed(Key::F11, KeyRepeat::No) {
+            let fullscreen = windowed.is_none();
+            match open_window(windowed.unwrap_or(screen_size), fullscreen) {
+                Ok(new_window) => {
+                    windowed = if fullscreen { Some((config.width, config.height)) } else { None };
+                    window   = new_window;
+                },
+                Err(e) => println!("Could not switch to {}: {}", if fullscreen { "fullscreen" } else { "a window" }, e),
+            }
+        }
+        if window.is_key_pressed(Key::E, KeyRepeat::No) {
+            export_view_exr(&config, View { upper_left, lower_right }, kernel);
+        }
+        // Contour lines of the view, as SVG.
+        if window.is_key_pressed(Key::B, KeyRepeat::No) {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            let path      = format!("mandelbrot_{}.svg", timestamp);
+            if let Err(e) = export_view_svg(&config, View { upper_left, lower_right }, kernel, CONTOUR_LEVELS, &path) {
+                println!("Failed to export {}: {}", path, e);
+            }
+        }
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            let path = screenshot_path(View { upper_left, lower_right }, config.limit, "png");
+            match current_frame(&config) {
+                Some(frame) => save_screenshot(&frame, config.width, config.height, &path),
+                None        => println!("No frame at the new window size yet."),
+            }
+        }
+        if window.is_key_pressed(Key::O, KeyRepeat::No) {
+            let path = screenshot_path(View { upper_left, lower_right }, config.limit, "ppm");
+            match current_frame(&config).map(|frame| write_ppm(&frame, config.width, config.height, &path)) {
+                Some(Ok(())) => println!("Saved {}.", path),
+                Some(Err(e)) => println!("Failed to save {}: {}", path, e),
+                None         => println!("No frame at the new window size yet."),
+            }
+        }
+        // The wheel zooms around the point under the cursor.
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            if let Some((x, y)) = window.get_mouse_pos(MouseMode::Discard) {
+                if scroll != 0.0 {
+                    let view    = View { upper_left, lower_right };
+                    let cursor  = pixel_to_point((config.width, config.height), (x as usize, y as usize),
+                                                 upper_left, lower_right, 1.0, rotation);
+                    let zoomed  = zoom_view(view, cursor, WHEEL_ZOOM.powf(scroll as f64));
+                    upper_left  = zoomed.upper_left;
+                    lower_right = zoomed.lower_right;
+                    need_update = true;
+                }
+            }
+        }
+        let step     = pan_step(View { upper_left, lower_right });
+        let nudge    = julia_step(View { upper_left, lower_right });
+        // Every held key moves the view once per frame and all of them share
+        // the single post below, which supersedes whatever is still rendering.
+        // Keys that don't move the view must not post, or holding one would
+        // restart the accumulation every frame. The pan is applied first and
+        // the zoom keeps the panned center, whatever order the keys come in.
+        let mut pan  = Complex { re: 0.0, im: 0.0 };
+        let mut zoom = 1.0;
+        for k in window.get_keys() {
+            match k {
+                Key::Left  => pan.re -= step,
+                Key::A     => pan.re -= step,
+                Key::Right => pan.re += step,
+                Key::D     => pan.re += step,
+                Key::Up    => pan.im += step,
+                Key::Down  => pan.im -= step,
+                Key::W     => zoom *= KEY_ZOOM,
+                Key::S     => zoom /= KEY_ZOOM,
+                // The keypad diamond nudges the Julia constant; outside Julia
+                // mode it does nothing.
+                Key::NumPad4 | Key::NumPad6 | Key::NumPad8 | Key::NumPad2 => match fractal {
+                    FractalKind::Julia { ref mut c } => match k {
+                        Key::NumPad4 => c.re -= nudge,
+                        Key::NumPad6 => c.re += nudge,
+                        Key::NumPad8 => c.im += nudge,
+                        _            => c.im -= nudge,
+                    },
+                    _ => continue,
+                },
+                _          => continue,
+            }
+            need_update = true;
+        }
+        upper_left  += pan;
+        lower_right += pan;
+        if zoom != 1.0 {
+            let view    = View { upper_left, lower_right };
+            let zoomed  = zoom_view(view, view_center(view), zoom);
+            upper_left  = zoomed.upper_left;
+            lower_right = zoomed.lower_right;
+        }
+        if need_update {
+            invalidate_ui_cache();
+            let job = RenderJob { view: View { upper_left, lower_right }, kernel, coloring, palette, rotation, fractal,
+                                  size: (config.width, config.height), supersample: config.supersample,
+                                  limit: config.limit, compare, cell: None };
+            post_job(&queue, job, grid);
+        }
+        // After a resize the front buffer keeps the last frame at the old size
+        // until the render thread presents one at the new size. minifb would
+        // reject it, so only events are pumped meanwhile and the window keeps
+        // showing that last frame instead of going black.
+        let presentable = {
+            let front = GlobalBuffer.lock().unwrap();
+            screen.clear();
+            screen.extend_from_slice(&front);
+            front.len() == config.width * config.height
+        };
+        if presentable {
+            if heatmap {
+                draw_heatmap(&mut screen, config.width, &GlobalTiles.lock().unwrap());
+            }
+            if references {
+                let reference = *GlobalReference.lock().unwrap();
+                draw_reference(&mut screen, config.width, &GlobalTiles.lock().unwrap(), reference);
+            }
+            composite_ui(&mut screen);
+            if crosshair {
+                draw_crosshair(&mut screen, config.width, config.height);
+            }
+            if let Some((start, end)) = selection {
+                draw_selection(&mut screen, config.width, config.height, start, end);
+            }
+            // A display that goes away mid-run ends the session like closing
+            // the window would.
+            if let Err(e) = window.update_with_buffer(&screen, config.width, config.height) {
+                println!("Lost the window: {}", e);
+                break;
+            }
+        } else {
+            window.update();
+        }
     }
+    shutdown_imgui();
+    Ok(())
 }
@@ -0,0 +1,140 @@
+use mandelbrot::{Config, FractalKind, View};
+use mandelbrot::{fit_bounds_to_aspect, pixel_to_point, view_center};
+use num::Complex;
+use std::cmp;
+use std::f32;
+use std::f64;
+
+// Range [ and ] keep the base iteration limit in.
+const MIN_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 10000;
+
+// Steps the limit by a quarter either way, so a few presses cover the range
+// at any magnitude.
+pub fn adjust_limit(limit: u32, up: bool) -> u32 {
+    let step  = cmp::max(limit / 4, 1);
+    let limit = if up { limit.saturating_add(step) } else { limit.saturating_sub(step) };
+    cmp::min(cmp::max(limit, MIN_LIMIT), MAX_LIMIT)
+}
+
+// Radians per , or . press.
+pub const ROTATION_STEP: f64 = f64::consts::PI / 90.0;
+
+// Fraction of the view width one pan key press moves, which keeps the pan a
+// constant number of pixels however the current zoom was reached.
+const PAN_FRACTION: f64 = 0.003;
+
+// The view the viewer starts on for `fractal`, fitted to the current window;
+// Home and J return to it.
+pub fn home_view(fractal: FractalKind, config: &Config) -> View {
+    let view = fractal.initial_view();
+    fit_bounds_to_aspect(view.upper_left, view.lower_right, config.width, config.height)
+}
+
+// Keeps the center and the plane distance per pixel when the window goes from
+// `from` to `to` pixels, so a resize shows more or less of the plane rather
+// than stretching it.
+pub fn resize_view(view: View, from: (usize, usize), to: (usize, usize)) -> View {
+    let center = view_center(view);
+    let half_w = (view.lower_right.re - view.upper_left.re) * to.0 as f64 / from.0 as f64 / 2.0;
+    let half_h = (view.upper_left.im - view.lower_right.im) * to.1 as f64 / from.1 as f64 / 2.0;
+    View {
+        upper_left:  Complex {re: center.re - half_w, im: center.im + half_h},
+        lower_right: Complex {re: center.re + half_w, im: center.im - half_h},
+    }
+}
+
+pub fn pan_step(view: View) -> f64 {
+    (view.lower_right.re - view.upper_left.re).abs() * PAN_FRACTION
+}
+
+// Share of the pan step a Julia nudge key moves c per frame. The set changes
+// shape much faster than it moves, so c creeps where the view would glide.
+const JULIA_NUDGE_FRACTION: f64 = 0.25;
+
+pub fn julia_step(view: View) -> f64 {
+    pan_step(view) * JULIA_NUDGE_FRACTION
+}
+
+// View scale per wheel notch, and per frame a W/S key is held.
+pub const WHEEL_ZOOM: f64 = 1.1;
+pub const KEY_ZOOM:   f64 = 1.01;
+
+// Boxes narrower than this many pixels both ways are a click, not a selection.
+const MIN_SELECTION: f32 = 4.0;
+
+// The view that fills the window with the box between `start` and `end`. The
+// box grows along its short side to the window's aspect, so everything
+// selected stays in view.
+pub fn selection_view(view:     View,
+                      rotation: f64,
+                      size:     (usize, usize),
+                      start:    (f32, f32),
+                      end:      (f32, f32)) -> Option<View> {
+    let (width, height) = ((end.0 - start.0).abs(), (end.1 - start.1).abs());
+    if width < MIN_SELECTION && height < MIN_SELECTION {
+        return None;
+    }
+    let width  = width.max(height * size.0 as f32 / size.1 as f32);
+    let middle = (((start.0 + end.0) / 2.0).max(0.0) as usize, ((start.1 + end.1) / 2.0).max(0.0) as usize);
+    let center = pixel_to_point(size, middle, view.upper_left, view.lower_right, 1.0, rotation);
+    let half   = (view.lower_right.re - view.upper_left.re) * width as f64 / size.0 as f64 / 2.0;
+    Some(fit_bounds_to_aspect(Complex { re: center.re - half, im: center.im },
+                              Complex { re: center.re + half, im: center.im }, size.0, size.1))
+}
+
+// Shrinks the view by `factor` around `point`, which stays on the same pixel.
+// Scaling the corners around a point commutes with the rotation around the
+// view center in pixel_to_point, so this holds for rotated views too.
+pub fn zoom_view(view: View, point: Complex<f64>, factor: f64) -> View {
+    let towards = |corner: Complex<f64>| Complex {
+        re: point.re + (corner.re - point.re) / factor,
+        im: point.im + (corner.im - point.im) / factor,
+    };
+    View { upper_left: towards(view.upper_left), lower_right: towards(view.lower_right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mandelbrot::{point_to_pixel, INITIAL_VIEW};
+
+    // How many pixels one pan key press moves the plane under the window.
+    fn pan_pixels(view: View, size: (usize, usize)) -> f64 {
+        let step   = pan_step(view);
+        let moved  = View {
+            upper_left:  Complex { re: view.upper_left.re + step,  im: view.upper_left.im },
+            lower_right: Complex { re: view.lower_right.re + step, im: view.lower_right.im },
+        };
+        let center = view_center(view);
+        let before = point_to_pixel(size, center, view.upper_left, view.lower_right, 1.0, 0.0);
+        let after  = point_to_pixel(size, center, moved.upper_left, moved.lower_right, 1.0, 0.0);
+        before.0 - after.0
+    }
+
+    #[test]
+    fn julia_step_follows_zoom() {
+        let home   = INITIAL_VIEW;
+        let zoomed = zoom_view(home, view_center(home), 1024.0);
+        assert!((julia_step(home) / julia_step(zoomed) - 1024.0).abs() < 1e-6);
+        assert_eq!(julia_step(home), pan_step(home) * JULIA_NUDGE_FRACTION);
+    }
+
+    #[test]
+    fn pan_moves_same_pixels_at_every_zoom() {
+        let size     = (800, 600);
+        let home     = fit_bounds_to_aspect(INITIAL_VIEW.upper_left, INITIAL_VIEW.lower_right, size.0, size.1);
+        let expected = PAN_FRACTION * size.0 as f64;
+        let target   = Complex { re: -0.7435, im: 0.1314 };
+        let mut wheel = home;
+        for notches in 0 .. 200 {
+            // Once notch by notch, once in a single jump like a bookmark.
+            let jump = zoom_view(home, target, WHEEL_ZOOM.powi(notches));
+            for &view in &[wheel, jump] {
+                let pixels = pan_pixels(view, size);
+                assert!((pixels - expected).abs() < 1e-3, "{} pixels after {} notches", pixels, notches);
+            }
+            wheel = zoom_view(wheel, target, WHEEL_ZOOM);
+        }
+    }
+}
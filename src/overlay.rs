@@ -0,0 +1,132 @@
+use mandelbrot::{ReferenceMark, TileTiming};
+use std::cmp;
+use std::f32;
+
+// One-pixel lines through the window center. XOR keeps them visible over any
+// palette, the overlay included.
+pub fn draw_crosshair(buffer: &mut [u32], width: usize, height: usize) {
+    let (cx, cy) = (width / 2, height / 2);
+    for x in 0 .. width {
+        buffer[cy * width + x] ^= 0x00FF_FFFF;
+    }
+    for y in (0 .. height).filter(|&y| y != cy) {
+        buffer[y * width + cx] ^= 0x00FF_FFFF;
+    }
+}
+
+// Outline of the box between two window positions, XORed like the crosshair.
+pub fn draw_selection(buffer: &mut [u32], width: usize, height: usize, start: (f32, f32), end: (f32, f32)) {
+    let clamp    = |v: f32, len: usize| cmp::min(v.max(0.0) as usize, len - 1);
+    let (x0, x1) = (clamp(start.0.min(end.0), width),  clamp(start.0.max(end.0), width));
+    let (y0, y1) = (clamp(start.1.min(end.1), height), clamp(start.1.max(end.1), height));
+    for x in x0 ..= x1 {
+        buffer[y0 * width + x] ^= 0x00FF_FFFF;
+        if y1 != y0 {
+            buffer[y1 * width + x] ^= 0x00FF_FFFF;
+        }
+    }
+    for y in y0 + 1 .. y1 {
+        buffer[y * width + x0] ^= 0x00FF_FFFF;
+        if x1 != x0 {
+            buffer[y * width + x1] ^= 0x00FF_FFFF;
+        }
+    }
+}
+
+// Tints every tile half way towards green for the cheapest, red for the most
+// expensive one of the frame.
+pub fn draw_heatmap(buffer: &mut [u32], width: usize, timings: &[TileTiming]) {
+    let slowest = timings.iter().fold(0.0f32, |max, t| max.max(t.ms));
+    if slowest <= 0.0 {
+        return;
+    }
+    let height = buffer.len() / cmp::max(width, 1);
+    for tile in timings {
+        let cost = tile.ms / slowest;
+        let tint = ((cost * 255.0) as u32) << 16 | (((1.0 - cost) * 255.0) as u32) << 8;
+        for y in tile.top .. cmp::min(tile.top + tile.height, height) {
+            for x in tile.left .. cmp::min(tile.left + tile.width, width) {
+                let pixel = &mut buffer[y * width + x];
+                *pixel    = ((*pixel >> 1) & 0x007F_7F7F) + ((tint >> 1) & 0x007F_7F7F);
+            }
+        }
+    }
+}
+
+// Half the span of the cross marking the reference point.
+const REFERENCE_MARK: usize = 6;
+
+// Prints each tile's delta-iterations against the perturbation reference in
+// its corner, red for tiles with glitched pixels, and XORs a cross over the
+// reference point. Draws nothing off the perturbation path.
+pub fn draw_reference(buffer: &mut [u32], width: usize, timings: &[TileTiming], reference: Option<ReferenceMark>) {
+    let reference = match reference {
+        Some(reference) => reference,
+        None            => return,
+    };
+    let height = buffer.len() / cmp::max(width, 1);
+    for tile in timings {
+        let color = if tile.glitches > 0 { 0x00FF_4040 } else { 0x00FF_FFFF };
+        draw_text(buffer, width, (tile.left + 2, tile.top + 2), &format!("{:+.0}", tile.delta), color);
+    }
+    let (cx, cy) = (reference.pixel.0 as usize, reference.pixel.1 as usize);
+    if cx >= width || cy >= height {
+        return;
+    }
+    for x in cx.saturating_sub(REFERENCE_MARK) ..= cmp::min(cx + REFERENCE_MARK, width - 1) {
+        buffer[cy * width + x] ^= 0x00FF_FFFF;
+    }
+    for y in (cy.saturating_sub(REFERENCE_MARK) ..= cmp::min(cy + REFERENCE_MARK, height - 1)).filter(|&y| y != cy) {
+        buffer[y * width + cx] ^= 0x00FF_FFFF;
+    }
+}
+
+// 3x5 glyphs, one row per byte with the leftmost pixel in bit 2. Covers what
+// the frame annotations print; anything else draws blank.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        'i' => [0b010, 0b000, 0b010, 0b010, 0b010],
+        'n' => [0b000, 0b110, 0b101, 0b101, 0b101],
+        'x' => [0b000, 0b000, 0b101, 0b010, 0b101],
+        _   => [0; 5],
+    }
+}
+
+const TEXT_SCALE: usize = 2;
+
+pub fn draw_text(pixels: &mut [u32], width: usize, origin: (usize, usize), text: &str, color: u32) {
+    let height = pixels.len() / width;
+    for (i, c) in text.chars().enumerate() {
+        let left = origin.0 + i * 4 * TEXT_SCALE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0 .. 3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0 .. TEXT_SCALE {
+                    for dx in 0 .. TEXT_SCALE {
+                        let x = left + column * TEXT_SCALE + dx;
+                        let y = origin.1 + row * TEXT_SCALE + dy;
+                        if x < width && y < height {
+                            pixels[y * width + x] = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
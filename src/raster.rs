@@ -0,0 +1,271 @@
+use std::cmp;
+use std::f32;
+
+#[repr(C)]
+pub struct Point2DF {
+    x: f32,
+    y: f32,
+}
+
+// How triangle colors combine with what is already in the UI layer. imgui
+// emits straight-alpha vertex colors and expects SrcAlpha, OneMinusSrcAlpha;
+// Premultiplied is One, OneMinusSrcAlpha for sources that are premultiplied
+// already.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendMode {
+    Straight,
+    Premultiplied,
+}
+
+// Copy of imgui's alpha-only font texture, which every textured triangle
+// samples; see load_font_atlas.
+pub struct FontAtlas {
+    pub alpha:  Vec<u8>,
+    pub width:  usize,
+    pub height: usize,
+}
+
+impl FontAtlas {
+    // Nearest texel at `uv`. Before the atlas is loaded everything samples
+    // as opaque, which is how untextured geometry looks anyway.
+    fn sample(&self, u: f32, v: f32) -> f32 {
+        if self.alpha.is_empty() {
+            return 1.0;
+        }
+        let x = cmp::min((u * self.width  as f32).max(0.0) as usize, self.width  - 1);
+        let y = cmp::min((v * self.height as f32).max(0.0) as usize, self.height - 1);
+        self.alpha[y * self.width + x] as f32 / 255.0
+    }
+}
+
+// Rasterized imgui overlay, kept between frames and only redrawn when the
+// draw lists hash differently. Pixels are premultiplied 0xAARRGGBB, so
+// blending triangles into the layer and the layer over the fractal are both
+// One, OneMinusSrcAlpha and antialiased text edges pick up no dark fringe.
+pub struct UiLayer {
+    pub pixels: Vec<u32>,
+    pub width:  usize,
+    pub hash:   Option<u64>,
+    pub blend:  BlendMode,
+}
+
+#[inline]
+fn edge_function(p0: &Point2DF, p1: &Point2DF, p2: &Point2DF) -> f32 {
+    (p1.x - p0.x) * (p2.y - p0.y) - (p1.y - p0.y) * (p2.x - p0.x)
+}
+
+// Top-left fill rule: a pixel exactly on an edge shared by two triangles
+// belongs to the one for which the edge is a top or a left edge, so it is
+// drawn once. With edge_function positive inside, a top edge runs in +x and a
+// left edge runs in -y.
+#[inline]
+fn is_top_left(from: &Point2DF, to: &Point2DF) -> bool {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    dy < 0.0 || (dy == 0.0 && dx > 0.0)
+}
+
+#[inline]
+fn covers(w: f32, top_left: bool) -> bool {
+    w > 0.0 || (w == 0.0 && top_left)
+}
+
+#[inline]
+fn min3(x: f32, y: f32, z: f32) -> f32 {
+    let mut min = x;
+    if y < min { min = y; }
+    if z < min { min = z; }
+    min
+}
+
+#[inline]
+fn max3(x: f32, y: f32, z: f32) -> f32 {
+    let mut max = x;
+    if y > max { max = y; }
+    if z > max { max = z; }
+    max
+}
+
+// Interpolated vertex colors can land slightly outside 0..1; clamp so a channel
+// never carries into its neighbour when packed.
+#[inline]
+fn unit_to_channel(value: f32) -> u32 {
+    (value * 255.0).max(0.0).min(255.0) as u32
+}
+
+// Premultiplied source-over: dst * (1 - src alpha) + src, per channel
+// including alpha.
+#[inline]
+pub fn blend_over(src: u32, dst: u32) -> u32 {
+    let inverse = 255 - (src >> 24);
+    let mut out = 0;
+    for shift in &[0, 8, 16, 24] {
+        let s = (src >> shift) & 0xFF;
+        let d = (dst >> shift) & 0xFF;
+        out  |= cmp::min(s + (d * inverse + 127) / 255, 255) << shift;
+    }
+    out
+}
+
+// What the rasterizer draws into and samples from. fetch_render_data locks
+// both once per frame and hands this to every draw_triangle call.
+pub struct RasterTarget<'a> {
+    pub layer: &'a mut UiLayer,
+    pub atlas: &'a FontAtlas,
+}
+
+// Channel names follow the vertex colors imgui hands the C++ side.
+#[allow(non_snake_case)]
+pub fn draw_triangle(target: &mut RasterTarget,
+                     p0: &Point2DF, p1: &Point2DF, p2: &Point2DF,
+                     R0: f32, G0: f32, B0: f32, A0: f32,
+                     R1: f32, G1: f32, B1: f32, A1: f32,
+                     R2: f32, G2: f32, B2: f32, A2: f32,
+                     uv0: &Point2DF, uv1: &Point2DF, uv2: &Point2DF) {
+    let layer = &mut *target.layer;
+    let atlas = target.atlas;
+    let area  = edge_function(p0, p1, p2);
+    let min_x = min3(p0.x, p1.x, p2.x);
+    let max_x = max3(p0.x, p1.x, p2.x);
+    let min_y = min3(p0.y, p1.y, p2.y);
+    let max_y = max3(p0.y, p1.y, p2.y);
+    let edges = (is_top_left(p1, p2), is_top_left(p2, p0), is_top_left(p0, p1));
+
+    // Windows may hang past the edge of the framebuffer, so the bounding box
+    // is clipped to it before scanning.
+    let rows     = layer.pixels.len() / cmp::max(layer.width, 1);
+    let clip     = |v: f32, end: usize| cmp::min(v.ceil().max(0.0) as usize, end);
+    let (x0, x1) = (clip(min_x, layer.width), clip(max_x, layer.width));
+    let (y0, y1) = (clip(min_y, rows), clip(max_y, rows));
+    // The edge functions are linear, so each one only moves by a constant per
+    // pixel in x and per row in y: evaluate them once at the top-left corner of
+    // the box and step from there. For imgui's pixel-aligned shapes the steps
+    // are small integers, so the sums stay exact and the fill rule holds.
+    let step_x   = (p1.y - p2.y, p2.y - p0.y, p0.y - p1.y);
+    let step_y   = (p2.x - p1.x, p0.x - p2.x, p1.x - p0.x);
+    let corner   = Point2DF {x: x0 as f32, y: y0 as f32};
+    let mut row  = (edge_function(p1, p2, &corner),
+                    edge_function(p2, p0, &corner),
+                    edge_function(p0, p1, &corner));
+    for y in y0 .. y1 {
+        let (mut e0, mut e1, mut e2) = row;
+        for x in x0 .. x1 {
+            if covers(e0, edges.0) && covers(e1, edges.1) && covers(e2, edges.2) {
+                let w0 = e0 / area;
+                let w1 = e1 / area;
+                let w2 = e2 / area;
+
+                // Glyphs are coverage in the atlas; solid shapes map to its
+                // white texel, so multiplying always works.
+                let u     = w0 * uv0.x + w1 * uv1.x + w2 * uv2.x;
+                let v     = w0 * uv0.y + w1 * uv1.y + w2 * uv2.y;
+                let alpha = ((w0 * A0 + w1 * A1 + w2 * A2) * atlas.sample(u, v)).max(0.0).min(1.0);
+                let pixel_index = y * layer.width + x;
+                let scale = if layer.blend == BlendMode::Straight { alpha } else { 1.0 };
+                let MeshR = unit_to_channel((w0 * R0 + w1 * R1 + w2 * R2) * scale);
+                let MeshG = unit_to_channel((w0 * G0 + w1 * G1 + w2 * G2) * scale);
+                let MeshB = unit_to_channel((w0 * B0 + w1 * B1 + w2 * B2) * scale);
+                let MeshA = unit_to_channel(alpha);
+
+                let background_color = layer.pixels[pixel_index];
+
+                layer.pixels[pixel_index] = blend_over(MeshA << 24 | MeshR << 16 | MeshG << 8 | MeshB,
+                                                       background_color);
+            }
+            e0 += step_x.0;
+            e1 += step_x.1;
+            e2 += step_x.2;
+        }
+        row = (row.0 + step_y.0, row.1 + step_y.1, row.2 + step_y.2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32) -> Point2DF {
+        Point2DF { x, y }
+    }
+
+    fn layer(width: usize, height: usize, blend: BlendMode) -> UiLayer {
+        UiLayer { pixels: vec![0; width * height], width, hash: None, blend }
+    }
+
+    // Untextured, so the empty atlas samples as opaque everywhere.
+    fn fill(layer: &mut UiLayer, p: [(f32, f32); 3], rgba: [f32; 4]) {
+        let atlas = FontAtlas { alpha: Vec::new(), width: 0, height: 0 };
+        let uv    = point(0.0, 0.0);
+        let [r, g, b, a] = rgba;
+        draw_triangle(&mut RasterTarget { layer, atlas: &atlas },
+                      &point(p[0].0, p[0].1), &point(p[1].0, p[1].1), &point(p[2].0, p[2].1),
+                      r, g, b, a, r, g, b, a, r, g, b, a, &uv, &uv, &uv);
+    }
+
+    #[test]
+    fn channels_clamp_within_their_byte() {
+        assert_eq!(unit_to_channel(1.0), 255);
+        assert_eq!(unit_to_channel(256.0 / 255.0), 255);
+        assert_eq!(unit_to_channel(-0.01), 0);
+        // Vertex colors just past 1.0 interpolate to 256 before the clamp,
+        // which would carry into the next channel up.
+        let mut target = layer(16, 16, BlendMode::Premultiplied);
+        fill(&mut target, [(0.0, 0.0), (16.0, 0.0), (0.0, 16.0)], [1.004; 4]);
+        assert!(target.pixels.iter().any(|&p| p != 0));
+        for &pixel in target.pixels.iter().filter(|&&p| p != 0) {
+            assert_eq!(pixel, 0xFFFF_FFFF);
+        }
+    }
+
+    #[test]
+    fn glyph_edge_blends_without_fringe() {
+        assert_eq!(blend_over(0, 0xFF20_4060), 0xFF20_4060);
+        assert_eq!(blend_over(0xFFFF_FFFF, 0xFF20_4060), 0xFFFF_FFFF);
+        // A half-covered texel at the edge of a white glyph, drawn with
+        // straight vertex alpha and again with premultiplied vertex colors.
+        let atlas = FontAtlas { alpha: vec![128], width: 1, height: 1 };
+        let uv    = point(0.5, 0.5);
+        for &(blend, rgb) in &[(BlendMode::Straight, 1.0), (BlendMode::Premultiplied, 128.0 / 255.0)] {
+            let mut target = layer(4, 4, blend);
+            draw_triangle(&mut RasterTarget { layer: &mut target, atlas: &atlas },
+                          &point(0.0, 0.0), &point(8.0, 0.0), &point(0.0, 8.0),
+                          rgb, rgb, rgb, 1.0, rgb, rgb, rgb, 1.0, rgb, rgb, rgb, 1.0, &uv, &uv, &uv);
+            assert_eq!(target.pixels[0], 0x8080_8080, "{:?}", blend);
+            // Over the fractal the edge lands halfway between white and the
+            // background, never darker than the background.
+            assert_eq!(blend_over(target.pixels[0], 0xFF20_4060) & 0x00FF_FFFF, 0x0090_A0B0, "{:?}", blend);
+        }
+    }
+
+    #[test]
+    fn stepped_edges_cover_same_pixels_as_per_pixel() {
+        let triangles = [[(0.0, 0.0), (16.0, 0.0), (0.0, 16.0)],
+                         [(16.0, 0.0), (16.0, 16.0), (0.0, 16.0)],
+                         [(1.5, 2.25), (30.75, 5.5), (7.25, 28.0)],
+                         [(-6.0, 3.0), (40.0, -2.0), (12.5, 37.5)],
+                         [(3.0, 3.0), (29.0, 3.0), (16.0, 3.5)]];
+        for p in &triangles {
+            let mut target = layer(32, 32, BlendMode::Premultiplied);
+            fill(&mut target, *p, [1.0; 4]);
+            assert!(target.pixels.iter().any(|&pixel| pixel != 0));
+            let (p0, p1, p2) = (point(p[0].0, p[0].1), point(p[1].0, p[1].1), point(p[2].0, p[2].1));
+            let edges        = (is_top_left(&p1, &p2), is_top_left(&p2, &p0), is_top_left(&p0, &p1));
+            for (i, &pixel) in target.pixels.iter().enumerate() {
+                let at = point((i % 32) as f32, (i / 32) as f32);
+                let covered = covers(edge_function(&p1, &p2, &at), edges.0)
+                    && covers(edge_function(&p2, &p0, &at), edges.1)
+                    && covers(edge_function(&p0, &p1, &at), edges.2);
+                assert_eq!(pixel != 0, covered, "{:?} at {}", p, i);
+            }
+        }
+    }
+
+    #[test]
+    fn alpha_byte_only_holds_coverage() {
+        let mut target = layer(16, 16, BlendMode::Straight);
+        fill(&mut target, [(0.0, 0.0), (16.0, 0.0), (0.0, 16.0)], [1.004, 1.004, 1.004, 0.5]);
+        assert!(target.pixels.iter().any(|&p| p != 0));
+        for &pixel in target.pixels.iter().filter(|&&p| p != 0) {
+            assert_eq!(pixel >> 24, unit_to_channel(0.5));
+        }
+    }
+}
@@ -0,0 +1,718 @@
+use cli::NamedPalette;
+use mandelbrot::{CancelToken, ColoringMode, FractalKind, KernelWidth, ReferenceMark, RendererBuilder, TileTiming,
+                 TileTuner, View, view_center};
+use navigation::zoom_view;
+use std::cmp;
+use std::collections::VecDeque;
+use std::f32;
+use std::f64;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+lazy_static! {
+    // Empty until the first frame is presented.
+    pub static ref GlobalBuffer:    Mutex<Vec<u32>>               = Mutex::new(Vec::new());
+    // Tile costs of the frame in GlobalBuffer, for the heatmap.
+    pub static ref GlobalTiles:     Mutex<Vec<TileTiming>>        = Mutex::new(Vec::new());
+    // Its perturbation reference, for the reference overlay.
+    pub static ref GlobalReference: Mutex<Option<ReferenceMark>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderJob {
+    pub view:        View,
+    pub kernel:      KernelWidth,
+    pub coloring:    ColoringMode,
+    // Index into PALETTES.
+    pub palette:     usize,
+    pub rotation:    f64,
+    pub fractal:     FractalKind,
+    // Window size in pixels and samples per pixel edge; render threads
+    // rebuild their renderers when either changes.
+    pub size:        (usize, usize),
+    pub supersample: usize,
+    // Base iteration limit, before progressive refinement and the zoom bonus.
+    pub limit:       u32,
+    // When set, present the difference against this kernel instead.
+    pub compare:     Option<KernelWidth>,
+    // Set in grid mode, where `size` is the cell's.
+    pub cell:        Option<GridCell>,
+}
+
+// Where a grid mode job's frame goes: `origin` is its top left pixel in a
+// window of `window` pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GridCell {
+    pub origin: (usize, usize),
+    pub window: (usize, usize),
+}
+
+// Cells per grid edge.
+const GRID_SIDE: usize = 2;
+// How much deeper each cell zooms than the one before it, left to right and
+// top to bottom.
+const GRID_ZOOM: f64   = 4.0;
+
+// Grid mode shows `job`'s view at GRID_SIDE^2 depths around its center, as
+// independent jobs that render concurrently when there are threads for them.
+pub fn grid_jobs(job: RenderJob) -> Vec<RenderJob> {
+    let size   = (job.size.0 / GRID_SIDE, job.size.1 / GRID_SIDE);
+    let center = view_center(job.view);
+    (0 .. GRID_SIDE * GRID_SIDE).map(|index| RenderJob {
+        view: zoom_view(job.view, center, GRID_ZOOM.powi(index as i32)),
+        size,
+        cell: Some(GridCell { origin: (index % GRID_SIDE * size.0, index / GRID_SIDE * size.1), window: job.size }),
+        ..job
+    }).collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DiffStats {
+    pub max:  u32,
+    pub mean: f32,
+}
+
+// Published by the render thread for the Stats window.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct FrameStats {
+    pub render_ms: f32,
+    pub diff:      Option<DiffStats>,
+    // Fraction of the full iteration limit reached, when refining progressively.
+    pub progress:  Option<f32>,
+    // Pixels colorize flagged as numeric errors in the last frame.
+    pub errors:    usize,
+    // Iteration limit the frame was rendered with, which grows with zoom.
+    pub limit:     u32,
+    // Pixels the perturbation path iterated again because they glitched.
+    pub glitches:  u32,
+}
+
+// Source of RenderQueue generations. All queues present into GlobalBuffer,
+// so they count together.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+struct Slot<T> {
+    waiting:    VecDeque<T>,
+    cancel:     CancelToken,
+    generation: u64,
+    // Jobs render threads have picked up and not yet finished or abandoned,
+    // of any generation.
+    active:     usize,
+}
+
+// Job queue for `concurrency` render threads, each given a share of the
+// worker threads. A post is a batch of independent jobs, one view or the
+// cells of the grid, that share a generation. It supersedes everything
+// before it: jobs not yet picked up are dropped and the ones being rendered
+// are cancelled, so with the default of one thread only the latest view is
+// ever finished. A thread that finishes a superseded frame anyway finds its
+// generation stale when presenting it.
+pub struct RenderQueue<T> {
+    slot:        Mutex<Slot<T>>,
+    ready:       Condvar,
+    concurrency: usize,
+}
+
+impl<T> RenderQueue<T> {
+    pub fn new(concurrency: usize) -> RenderQueue<T> {
+        RenderQueue {
+            slot:        Mutex::new(Slot { waiting: VecDeque::new(), cancel: CancelToken::new(), generation: 0,
+                                           active: 0 }),
+            ready:       Condvar::new(),
+            concurrency: cmp::max(concurrency, 1),
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    // Worker threads for each render thread's renderer, so that together
+    // they use `threads`.
+    pub fn worker_threads(&self, threads: usize) -> usize {
+        cmp::max(threads / self.concurrency, 1)
+    }
+
+    pub fn post(&self, job: T) {
+        self.post_batch(vec![job]);
+    }
+
+    pub fn post_batch(&self, jobs: Vec<T>) {
+        let mut slot = self.slot.lock().unwrap();
+        slot.cancel.cancel();
+        slot.cancel     = CancelToken::new();
+        slot.generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        slot.waiting.clear();
+        slot.waiting.extend(jobs);
+        self.ready.notify_all();
+    }
+
+    fn hand_out(slot: &mut Slot<T>) -> Option<(T, CancelToken, u64)> {
+        let job      = slot.waiting.pop_front()?;
+        slot.active += 1;
+        Some((job, slot.cancel.clone(), slot.generation))
+    }
+
+    // For a thread still refining its job: a new one replaces it, otherwise
+    // it keeps going.
+    fn try_take(&self) -> Option<(T, CancelToken, u64)> {
+        let mut slot = self.slot.lock().unwrap();
+        let job      = RenderQueue::hand_out(&mut slot)?;
+        slot.active -= 1;
+        Some(job)
+    }
+
+    // Called by a thread done with its job, finished or cancelled, before it
+    // waits for the next one.
+    fn finish(&self) {
+        self.slot.lock().unwrap().active -= 1;
+    }
+
+    fn take(&self) -> (T, CancelToken, u64) {
+        let mut slot = self.slot.lock().unwrap();
+        loop {
+            if let Some(job) = RenderQueue::hand_out(&mut slot) {
+                return job;
+            }
+            slot = self.ready.wait(slot).unwrap();
+        }
+    }
+}
+
+// Number of jittered frames averaged while the view stays put.
+const TAA_FRAMES: u32 = 16;
+
+fn halton(index: u32, base: u32) -> f32 {
+    let mut f      = 1.0;
+    let mut result = 0.0;
+    let mut i      = index;
+    while i > 0 {
+        f      /= base as f32;
+        result += f * (i % base) as f32;
+        i      /= base;
+    }
+    result
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Sub-pixel sample offset for an accumulation frame; the first frame samples
+// pixel corners exactly like a plain render. The Halton points are shifted
+// by a seed-derived offset (modulo one), so equal seeds give identical frames
+// and different seeds give different but equally well spread ones.
+fn taa_jitter(frame: u32, seed: u64) -> (f32, f32) {
+    if frame == 0 {
+        return (0.0, 0.0);
+    }
+    let bits     = splitmix64(seed);
+    let shift_x  = (bits >> 40) as f32 / (1u64 << 24) as f32;
+    let shift_y  = ((bits >> 16) & 0xFF_FFFF) as f32 / (1u64 << 24) as f32;
+    ((halton(frame, 2) + shift_x).fract() - 0.5, (halton(frame, 3) + shift_y).fract() - 0.5)
+}
+
+// Running per-channel mean of the frames rendered for the current view.
+struct Accumulator {
+    mean:   Vec<[f32; 3]>,
+    frames: u32,
+}
+
+impl Accumulator {
+    fn new(len: usize) -> Accumulator {
+        Accumulator { mean: vec![[0.0; 3]; len], frames: 0 }
+    }
+
+    fn reset(&mut self) {
+        self.frames = 0;
+    }
+
+    fn add(&mut self, pixels: &[u32]) {
+        self.frames += 1;
+        let weight = 1.0 / self.frames as f32;
+        for (mean, pixel) in self.mean.iter_mut().zip(pixels.iter()) {
+            let rgb = [((pixel >> 16) & 0xFF) as f32, ((pixel >> 8) & 0xFF) as f32, (pixel & 0xFF) as f32];
+            for c in 0..3 {
+                mean[c] += (rgb[c] - mean[c]) * weight;
+            }
+        }
+    }
+
+    fn resolve(&self, pixels: &mut [u32]) {
+        for (pixel, mean) in pixels.iter_mut().zip(self.mean.iter()) {
+            *pixel = ((mean[0].round() as u32) << 16) + ((mean[1].round() as u32) << 8) + mean[2].round() as u32;
+        }
+    }
+}
+
+// Largest per-channel difference between two 0x00RRGGBB pixels.
+fn pixel_difference(a: u32, b: u32) -> u32 {
+    (0 .. 3).map(|c| {
+        let (ca, cb) = ((a >> (8 * c)) & 0xFF, (b >> (8 * c)) & 0xFF);
+        ca.abs_diff(cb)
+    }).max().unwrap_or(0)
+}
+
+fn diff_stats(a: &[u32], b: &[u32]) -> DiffStats {
+    let mut max = 0;
+    let mut sum = 0u64;
+    for (&pa, &pb) in a.iter().zip(b.iter()) {
+        let d = pixel_difference(pa, pb);
+        max   = cmp::max(max, d);
+        sum  += d as u64;
+    }
+    DiffStats { max, mean: sum as f32 / cmp::max(a.len(), 1) as f32 }
+}
+
+// Even a one-step difference should be visible, so it is amplified before
+// going through a black - red - yellow - white heat ramp.
+const DIFF_GAIN: u32 = 16;
+
+fn diff_buffers(a: &[u32], b: &[u32]) -> Vec<u32> {
+    a.iter().zip(b.iter()).map(|(&pa, &pb)| {
+        let heat  = cmp::min(pixel_difference(pa, pb) * DIFF_GAIN, 3 * 255);
+        let red   = cmp::min(heat, 255);
+        let green = cmp::min(heat.saturating_sub(255), 255);
+        let blue  = heat.saturating_sub(2 * 255);
+        red << 16 | green << 8 | blue
+    }).collect()
+}
+
+// Resolution divisor of the coarse preview.
+const COARSE_FACTOR: usize = 8;
+
+fn upscale_nearest(src: &[u32], src_size: (usize, usize), dst: &mut [u32], dst_size: (usize, usize)) {
+    for (i, pixel) in dst.iter_mut().enumerate().take(dst_size.0 * dst_size.1) {
+        let x  = cmp::min((i % dst_size.0) * src_size.0 / dst_size.0, src_size.0 - 1);
+        let y  = cmp::min((i / dst_size.0) * src_size.1 / dst_size.1, src_size.1 - 1);
+        *pixel = src[y * src_size.0 + x];
+    }
+}
+
+// Generation of the frame in GlobalBuffer, read and written with it locked.
+static PRESENTED: AtomicU64 = AtomicU64::new(0);
+
+// GlobalBuffer is only ever the front buffer of the last finished frame: the
+// render thread fills its own back buffer and trades it in here, and the main
+// loop composites the UI onto a copy. Neither side sees a half-written frame.
+// The caller gets the previous frame back to reuse as its back buffer, sized
+// for `job` again if that frame was from before a resize. A grid cell is
+// copied into its part of the window instead.
+// Render threads finish in any order, so a frame of an older generation than
+// the one up is stale and dropped. Returns whether `back` was presented.
+fn present(back: &mut Vec<u32>, job: &RenderJob, generation: u64) -> bool {
+    let mut front = GlobalBuffer.lock().unwrap();
+    if generation < PRESENTED.load(Ordering::Relaxed) {
+        return false;
+    }
+    PRESENTED.store(generation, Ordering::Relaxed);
+    let (width, height) = job.size;
+    match job.cell {
+        None       => {
+            mem::swap(&mut *front, back);
+            if back.len() != width * height {
+                *back = vec![0; width * height];
+            }
+        }
+        Some(cell) => {
+            if front.len() != cell.window.0 * cell.window.1 {
+                *front = vec![0; cell.window.0 * cell.window.1];
+            }
+            for (y, row) in back.chunks(width).take(height).enumerate() {
+                let start = (cell.origin.1 + y) * cell.window.0 + cell.origin.0;
+                front[start .. start + width].copy_from_slice(row);
+            }
+        }
+    }
+    true
+}
+
+// Frame time progressive refinement tries to stay under.
+const FRAME_BUDGET_MS:       f32 = 16.0;
+// Iteration limit the first frame of a new view starts from.
+const MIN_PROGRESSIVE_LIMIT: u32 = 32;
+
+// Raises the iteration limit over successive frames of one view instead of
+// paying for the full limit up front, so a moving view stays responsive and
+// deep detail fills in once it stops.
+pub struct Refinement {
+    limit:  u32,
+    target: u32,
+}
+
+impl Refinement {
+    pub fn new(target: u32) -> Refinement {
+        Refinement { limit: cmp::min(MIN_PROGRESSIVE_LIMIT, target), target }
+    }
+
+    fn restart(&mut self) {
+        self.limit = cmp::min(MIN_PROGRESSIVE_LIMIT, self.target);
+    }
+
+    fn progress(&self) -> f32 {
+        self.limit as f32 / self.target as f32
+    }
+
+    // Frame time grows roughly with the limit, so the next limit is whatever
+    // would have fit the budget this frame, but at least double so refinement
+    // always converges. Returns whether the limit rose.
+    fn advance(&mut self, frame_ms: f32) -> bool {
+        if self.limit >= self.target {
+            return false;
+        }
+        let fit    = (self.limit as f32 * FRAME_BUDGET_MS / frame_ms.max(0.001)) as u32;
+        self.limit = cmp::min(self.target, cmp::max(self.limit * 2, fit));
+        true
+    }
+}
+
+pub fn spawn_render_thread(queue:          Arc<RenderQueue<RenderJob>>,
+                           stats:          Arc<Mutex<FrameStats>>,
+                           builder:        RendererBuilder,
+                           palettes:       Arc<Vec<NamedPalette>>,
+                           mut tuner:      Option<TileTuner>,
+                           mut refinement: Option<Refinement>,
+                           zoom_preview:   bool,
+                           coarse_preview: bool) {
+    thread::spawn(move || {
+        let mut renderer  = builder.clone().build();
+        let mut reference = builder.clone().build();
+        let coarse_build  = |width: usize, height: usize| if coarse_preview {
+            Some(builder.clone().size(cmp::max(width / COARSE_FACTOR, 1), cmp::max(height / COARSE_FACTOR, 1))
+                 .supersample(1).build())
+        } else {
+            None
+        };
+        let mut coarse    = coarse_build(renderer.width, renderer.height);
+        println!("Iteration cache: {}, {} KB.", renderer.cache().name(), renderer.cache().memory_bytes() / 1024);
+        let len             = renderer.width * renderer.height;
+        let mut frame       = vec![0; len];
+        let mut back        = vec![0; len];
+        let mut accumulator = Accumulator::new(len);
+        let (mut job, mut cancel, mut generation) = queue.take();
+        // PALETTES index the renderers' lookup tables were built from.
+        let mut palette     = None;
+        // Whether the job has not had a pass yet, which is when the coarse
+        // preview goes up.
+        let mut fresh       = true;
+        loop {
+            if (renderer.width, renderer.height) != job.size || renderer.supersample != job.supersample {
+                let (width, height) = job.size;
+                let builder = builder.clone().size(width, height).supersample(job.supersample);
+                renderer    = builder.clone().build();
+                reference   = builder.build();
+                coarse      = coarse_build(width, height);
+                frame       = vec![0; width * height];
+                back        = vec![0; width * height];
+                accumulator = Accumulator::new(width * height);
+                palette     = None;
+            }
+            if palette != Some(job.palette) {
+                renderer.set_palette(&palettes[job.palette].1);
+                reference.set_palette(&palettes[job.palette].1);
+                if let Some(ref mut coarse) = coarse {
+                    coarse.set_palette(&palettes[job.palette].1);
+                }
+                palette = Some(job.palette);
+            }
+            let start  = Instant::now();
+            renderer.set_kernel(job.kernel);
+            renderer.set_rotation(job.rotation);
+            renderer.set_fractal(job.fractal);
+            renderer.set_coloring(job.coloring);
+            if let Some(ref tuner) = tuner {
+                renderer.set_tile_size(tuner.tile_size());
+            }
+            renderer.set_limit(job.limit);
+            reference.set_limit(job.limit);
+            if let Some(ref mut refinement) = refinement {
+                if refinement.target != job.limit {
+                    refinement.target = job.limit;
+                    refinement.restart();
+                }
+                renderer.set_limit(refinement.limit);
+            }
+            let jitter = taa_jitter(accumulator.frames, renderer.settings().jitter_seed);
+            // A new view first goes up at a fraction of the resolution, which
+            // costs next to nothing, while the full pass renders.
+            if let (true, Some(coarse)) = (fresh, coarse.as_mut()) {
+                coarse.set_settings(renderer.settings().clone());
+                coarse.set_coloring(job.coloring);
+                coarse.iterate(job.view, (0.0, 0.0), &cancel);
+                if !cancel.is_cancelled() {
+                    let mut small = vec![0; coarse.width * coarse.height];
+                    coarse.colorize(&mut small);
+                    upscale_nearest(&small, (coarse.width, coarse.height), &mut back, job.size);
+                    present(&mut back, &job, generation);
+                }
+            }
+            fresh = false;
+            renderer.iterate(job.view, jitter, &cancel);
+            // View whose iterations fill the cache completely, if any.
+            let cached_view = if cancel.is_cancelled() { None } else { Some(job.view) };
+            if let Some(ref mut tuner) = tuner {
+                if !cancel.is_cancelled() {
+                    if let Some(size) = tuner.record(start.elapsed().as_secs_f32() * 1000.0) {
+                        println!("Tile auto-tune: {} pixels.", size);
+                    }
+                }
+            }
+            if let Some(compare) = job.compare {
+                reference.set_kernel(compare);
+                reference.set_rotation(job.rotation);
+                reference.set_fractal(job.fractal);
+                reference.set_coloring(job.coloring);
+                reference.iterate(job.view, (0.0, 0.0), &cancel);
+            }
+            // A cancelled pass leaves the iteration cache half updated, so it
+            // must never reach the accumulator or the presented buffer.
+            let mut deepening = false;
+            if !cancel.is_cancelled() {
+                let mut diff = None;
+                let errors;
+                if job.compare.is_some() {
+                    errors = renderer.colorize(&mut frame);
+                    reference.colorize(&mut back);
+                    diff = Some(diff_stats(&frame, &back));
+                    back = diff_buffers(&frame, &back);
+                } else if job.coloring == ColoringMode::Raw {
+                    // Cheapest present path: no palette and no accumulation.
+                    errors = renderer.colorize(&mut back);
+                } else {
+                    errors = renderer.colorize(&mut frame);
+                    accumulator.add(&frame);
+                    accumulator.resolve(&mut back);
+                }
+                let render_ms = start.elapsed().as_secs_f32() * 1000.0;
+                if present(&mut back, &job, generation) {
+                    // Both are in window pixels, which a grid cell's tiles are not.
+                    let (tiles, mark) = match job.cell {
+                        None    => (renderer.tile_timings().to_vec(), renderer.reference()),
+                        Some(_) => (Vec::new(), None),
+                    };
+                    *GlobalTiles.lock().unwrap()     = tiles;
+                    *GlobalReference.lock().unwrap() = mark;
+                    *stats.lock().unwrap() = FrameStats {
+                        render_ms,
+                        diff,
+                        progress: refinement.as_ref().map(|r| r.progress()),
+                        errors,
+                        limit:    renderer.active_limit(),
+                        glitches: renderer.tile_timings().iter().map(|t| t.glitches).sum(),
+                    };
+                }
+                // A higher limit changes every pixel, so accumulation restarts.
+                if refinement.as_mut().map(|r| r.advance(render_ms)).unwrap_or(false) {
+                    accumulator.reset();
+                    deepening = true;
+                }
+            }
+            let refining = deepening || job.coloring != ColoringMode::Raw && job.compare.is_none()
+                && accumulator.frames < TAA_FRAMES;
+            let next     = if refining && !cancel.is_cancelled() {
+                queue.try_take()
+            } else {
+                queue.finish();
+                Some(queue.take())
+            };
+            if let Some((next_job, next_cancel, next_generation)) = next {
+                // A preview at the old size would not fit the resized window.
+                if let (true, Some(cached), true) = (zoom_preview, cached_view, next_job.size == job.size) {
+                    renderer.preview(cached, next_job.view, next_job.rotation, &mut back);
+                    present(&mut back, &next_job, next_generation);
+                }
+                job        = next_job;
+                cancel     = next_cancel;
+                generation = next_generation;
+                fresh      = true;
+                accumulator.reset();
+                if let Some(ref mut refinement) = refinement {
+                    refinement.restart();
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mandelbrot::{Formula, COLORS, INITIAL_VIEW};
+    use std::time::Duration;
+
+    // GlobalBuffer is shared, so tests that present take turns. Each starts
+    // from an empty front buffer.
+    static PRESENTING: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn rapid_posts_render_only_the_last() {
+        let queue = RenderQueue::new(1);
+        queue.post(0);
+        let (first, in_flight, old) = queue.take();
+        assert_eq!(first, 0);
+        for job in 1 .. 10 {
+            queue.post(job);
+        }
+        assert!(in_flight.is_cancelled());
+        let (last, cancel, generation) = queue.take();
+        assert_eq!(last, 9);
+        assert!(!cancel.is_cancelled());
+        assert!(generation > old);
+        assert!(queue.try_take().is_none());
+    }
+
+    #[test]
+    fn batches_wait_whole_and_supersede_each_other() {
+        let queue = RenderQueue::new(2);
+        queue.post_batch(vec![0, 1, 2]);
+        let (first, in_flight, old) = queue.take();
+        queue.post_batch(vec![3, 4, 5]);
+        assert_eq!(first, 0);
+        assert!(in_flight.is_cancelled());
+        let cells: Vec<_> = (0 .. 3).map(|_| queue.take()).collect();
+        assert_eq!(cells.iter().map(|c| c.0).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert!(cells.iter().all(|c| !c.1.is_cancelled() && c.2 == cells[0].2 && c.2 > old));
+        assert!(queue.try_take().is_none());
+        assert_eq!(queue.worker_threads(8), 4);
+        assert_eq!(queue.worker_threads(1), 1);
+    }
+
+    // What the render thread presents after `frames` accumulation frames of
+    // one view.
+    fn accumulate(seed: u64, frames: u32) -> Vec<u32> {
+        let mut renderer    = RendererBuilder::new().size(64, 48).limit(128).jitter_seed(seed).build();
+        let mut frame       = vec![0; 64 * 48];
+        let mut accumulator = Accumulator::new(64 * 48);
+        for index in 0 .. frames {
+            renderer.iterate(INITIAL_VIEW, taa_jitter(index, renderer.settings().jitter_seed), &CancelToken::new());
+            renderer.colorize(&mut frame);
+            accumulator.add(&frame);
+        }
+        accumulator.resolve(&mut frame);
+        frame
+    }
+
+    #[test]
+    fn jitter_seed_reproduces_accumulation() {
+        for frame in 0 .. TAA_FRAMES {
+            assert_eq!(taa_jitter(frame, 7), taa_jitter(frame, 7));
+            let (x, y) = taa_jitter(frame, 7);
+            assert!(x.abs() <= 0.5 && y.abs() <= 0.5);
+        }
+        assert!((1 .. TAA_FRAMES).all(|frame| taa_jitter(frame, 7) != taa_jitter(frame, 8)));
+        assert_eq!(accumulate(7, 4), accumulate(7, 4));
+        assert_ne!(accumulate(7, 4), accumulate(8, 4));
+    }
+
+    fn job(view: View, limit: u32) -> RenderJob {
+        RenderJob { view, kernel: KernelWidth::fastest(), coloring: ColoringMode::Raw, palette: 0, rotation: 0.0,
+                    fractal: FractalKind::Mandelbrot, size: (64, 48), supersample: 1, limit, compare: None,
+                    cell: None }
+    }
+
+    // The complete frame the render thread presents for `job`.
+    fn complete_frame(builder: &RendererBuilder, job: RenderJob) -> Vec<u32> {
+        let mut renderer = builder.clone().build();
+        renderer.set_kernel(job.kernel);
+        renderer.set_coloring(job.coloring);
+        renderer.set_limit(job.limit);
+        renderer.iterate(job.view, (0.0, 0.0), &CancelToken::new());
+        let mut pixels = vec![0; 64 * 48];
+        renderer.colorize(&mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn cancelled_render_never_presents_torn_frame() {
+        // The interpreted formula path has no interior shortcuts, so the slow
+        // job is still rendering when the fast one replaces it.
+        let _turn    = PRESENTING.lock().unwrap();
+        GlobalBuffer.lock().unwrap().clear();
+        let builder  = RendererBuilder::new().size(64, 48).formula(Formula::parse("z^3 + c").ok());
+        let queue    = Arc::new(RenderQueue::new(1));
+        let stats    = Arc::new(Mutex::new(FrameStats::default()));
+        let palettes = Arc::new(vec![("default".to_string(), COLORS.to_vec())]);
+        spawn_render_thread(queue.clone(), stats, builder.clone(), palettes, None, None, false, false);
+        let slow     = job(INITIAL_VIEW, 20000);
+        let fast     = job(INITIAL_VIEW, 16);
+        let (slow_frame, fast_frame) = (complete_frame(&builder, slow), complete_frame(&builder, fast));
+        assert_ne!(slow_frame, fast_frame);
+        queue.post(slow);
+        thread::sleep(Duration::from_millis(20));
+        queue.post(fast);
+        let start = Instant::now();
+        loop {
+            let frame = GlobalBuffer.lock().unwrap().clone();
+            let blank = frame.iter().all(|&p| p == 0);
+            assert!(blank || frame == slow_frame || frame == fast_frame, "torn frame presented");
+            if frame == fast_frame {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(30), "latest view never presented");
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn rapid_submits_keep_one_render_active_and_the_last_wins() {
+        let _turn    = PRESENTING.lock().unwrap();
+        GlobalBuffer.lock().unwrap().clear();
+        let builder  = RendererBuilder::new().size(64, 48).formula(Formula::parse("z^3 + c").ok());
+        let queue    = Arc::new(RenderQueue::new(1));
+        let stats    = Arc::new(Mutex::new(FrameStats::default()));
+        let palettes = Arc::new(vec![("default".to_string(), COLORS.to_vec())]);
+        spawn_render_thread(queue.clone(), stats, builder.clone(), palettes, None, None, false, false);
+        let center   = view_center(INITIAL_VIEW);
+        let jobs: Vec<_> = (0 .. 12).map(|k| job(zoom_view(INITIAL_VIEW, center, 1.0 + k as f64 / 4.0), 2000))
+                                    .collect();
+        for &job in &jobs {
+            queue.post(job);
+            assert!(queue.slot.lock().unwrap().active <= 1);
+            thread::sleep(Duration::from_millis(2));
+        }
+        let last  = complete_frame(&builder, jobs[jobs.len() - 1]);
+        let start = Instant::now();
+        while *GlobalBuffer.lock().unwrap() != last {
+            assert!(queue.slot.lock().unwrap().active <= 1);
+            assert!(start.elapsed() < Duration::from_secs(30), "latest view never presented");
+            thread::yield_now();
+        }
+        // Raw frames need no refinement, so the thread is done with the last
+        // view and idle.
+        while queue.slot.lock().unwrap().active > 0 {
+            assert!(start.elapsed() < Duration::from_secs(30), "finished render still counted as active");
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn grid_cells_present_in_place_and_stale_frames_drop() {
+        let _turn = PRESENTING.lock().unwrap();
+        GlobalBuffer.lock().unwrap().clear();
+        let cells = grid_jobs(job(INITIAL_VIEW, 16));
+        assert_eq!(cells.len(), GRID_SIDE * GRID_SIDE);
+        let stale      = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        for (index, cell) in cells.iter().enumerate() {
+            assert_eq!(cell.size, (64 / GRID_SIDE, 48 / GRID_SIDE));
+            assert!(present(&mut vec![index as u32 + 1; 32 * 24], cell, generation));
+        }
+        let front = GlobalBuffer.lock().unwrap().clone();
+        assert_eq!(front.len(), 64 * 48);
+        for (i, &pixel) in front.iter().enumerate() {
+            let (x, y) = (i % 64, i / 64);
+            assert_eq!(pixel, (y / 24 * GRID_SIDE + x / 32) as u32 + 1);
+        }
+        let mut old = vec![0xFF; 64 * 48];
+        assert!(!present(&mut old, &job(INITIAL_VIEW, 16), stale));
+        assert!(!present(&mut vec![0xFF; 32 * 24], &cells[0], stale));
+        assert_eq!(*GlobalBuffer.lock().unwrap(), front);
+        assert_eq!(old, vec![0xFF; 64 * 48]);
+    }
+}
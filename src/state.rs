@@ -0,0 +1,264 @@
+use mandelbrot::{ColoringMode, FractalKind, PrecisePoint, View, DEFAULT_JULIA_C};
+use minifb::Key;
+use num::Complex;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::f64;
+
+// A view stored under one of the number keys. The pan step follows from the
+// bounds, so only they, the rotation, the limit and the fractal are kept.
+// Bookmarks saved before rotation and fractal were stored load unrotated on
+// the Mandelbrot set.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    upper_left:  (f64, f64),
+    lower_right: (f64, f64),
+    #[serde(default)]
+    rotation:    f64,
+    limit:       u32,
+    #[serde(default = "mandelbrot_name")]
+    fractal:     String,
+    #[serde(default)]
+    julia_c:     Option<(f64, f64)>,
+}
+
+impl Bookmark {
+    pub fn capture(view: View, rotation: f64, limit: u32, fractal: FractalKind) -> Bookmark {
+        Bookmark {
+            upper_left:  (view.upper_left.re,  view.upper_left.im),
+            lower_right: (view.lower_right.re, view.lower_right.im),
+            rotation,
+            limit,
+            fractal:     fractal_name(fractal),
+            julia_c:     julia_c(fractal),
+        }
+    }
+
+    pub fn view(&self) -> View {
+        View {
+            upper_left:  Complex { re: self.upper_left.0,  im: self.upper_left.1 },
+            lower_right: Complex { re: self.lower_right.0, im: self.lower_right.1 },
+        }
+    }
+
+    pub fn rotation(&self) -> f64 {
+        self.rotation
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    pub fn fractal(&self) -> Result<FractalKind, String> {
+        fractal_from_name(&self.fractal, self.julia_c).ok_or_else(|| format!("unknown fractal {:?}", self.fractal))
+    }
+}
+
+fn mandelbrot_name() -> String {
+    fractal_name(FractalKind::Mandelbrot)
+}
+
+pub const BOOKMARKS_PATH: &'static str = "bookmarks.json";
+
+pub const BOOKMARK_KEYS: [Key; 9] = [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5,
+                                     Key::Key6, Key::Key7, Key::Key8, Key::Key9];
+
+// One slot per number key. A missing file starts with every slot empty; an
+// unreadable one too, after a warning, so a bad file never blocks the viewer.
+pub fn load_bookmarks(path: &str) -> Vec<Option<Bookmark>> {
+    let mut bookmarks: Vec<Option<Bookmark>> = match File::open(path) {
+        Ok(file) => serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+            println!("Ignoring bookmarks in {}: {}", path, e);
+            Vec::new()
+        }),
+        Err(_)   => Vec::new(),
+    };
+    bookmarks.resize(BOOKMARK_KEYS.len(), None);
+    bookmarks
+}
+
+pub fn save_bookmarks(path: &str, bookmarks: &[Option<Bookmark>]) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), bookmarks)?;
+    Ok(())
+}
+
+// Everything that decides what the viewer shows, for --load and K. Zoom and
+// pan steps follow from the bounds, so rotation is kept instead. Fractal and
+// coloring go by their overlay names, and julia_c is only set for Julia.
+// Views deeper than f64 corners place keep their center as decimals, and the
+// bounds are offsets from it; states saved before that have none.
+#[derive(Serialize, Deserialize)]
+pub struct ViewState {
+    upper_left:  (f64, f64),
+    lower_right: (f64, f64),
+    rotation:    f64,
+    limit:       u32,
+    fractal:     String,
+    julia_c:     Option<(f64, f64)>,
+    palette:     usize,
+    coloring:    String,
+    #[serde(default)]
+    center:      Option<(String, String)>,
+}
+
+pub const VIEW_STATE_PATH: &'static str = "state.json";
+
+impl ViewState {
+    // With a `center`, `view` is given as offsets from it.
+    pub fn capture(view:     View,
+                   center:   Option<&PrecisePoint>,
+                   rotation: f64,
+                   limit:    u32,
+                   fractal:  FractalKind,
+                   palette:  usize,
+                   coloring: ColoringMode) -> ViewState {
+        ViewState {
+            upper_left:  (view.upper_left.re,  view.upper_left.im),
+            lower_right: (view.lower_right.re, view.lower_right.im),
+            rotation,
+            limit,
+            fractal:     fractal_name(fractal),
+            julia_c:     julia_c(fractal),
+            palette,
+            coloring:    coloring.name().trim_end_matches('\0').to_string(),
+            center:      center.map(PrecisePoint::to_strings),
+        }
+    }
+
+    // Checks the whole state before touching any of the live variables, so a
+    // bad file leaves the view as it was. `view` gets offsets from `center`
+    // when the state has one.
+    pub fn apply(&self,
+                 view:     &mut View,
+                 center:   &mut Option<PrecisePoint>,
+                 rotation: &mut f64,
+                 limit:    &mut u32,
+                 fractal:  &mut FractalKind,
+                 palette:  &mut usize,
+                 coloring: &mut ColoringMode,
+                 palettes: usize) -> Result<(), String> {
+        let kind = fractal_from_name(&self.fractal, self.julia_c)
+            .ok_or_else(|| format!("unknown fractal {:?}", self.fractal))?;
+        let mode = coloring_from_name(&self.coloring)
+            .ok_or_else(|| format!("unknown coloring {:?}", self.coloring))?;
+        if self.palette >= palettes {
+            return Err(format!("palette {} out of range, {} loaded", self.palette, palettes));
+        }
+        let point = match self.center {
+            Some((ref re, ref im)) => Some(PrecisePoint::parse(re, im)?),
+            None                   => None,
+        };
+        *view     = View {
+            upper_left:  Complex { re: self.upper_left.0,  im: self.upper_left.1 },
+            lower_right: Complex { re: self.lower_right.0, im: self.lower_right.1 },
+        };
+        *center   = point;
+        *rotation = self.rotation;
+        *limit    = self.limit;
+        *fractal  = kind;
+        *palette  = self.palette;
+        *coloring = mode;
+        Ok(())
+    }
+}
+
+fn fractal_name(fractal: FractalKind) -> String {
+    fractal.name().trim_end_matches('\0').to_string()
+}
+
+fn julia_c(fractal: FractalKind) -> Option<(f64, f64)> {
+    match fractal {
+        FractalKind::Julia { c } => Some((c.re, c.im)),
+        _                        => None,
+    }
+}
+
+fn fractal_from_name(name: &str, julia_c: Option<(f64, f64)>) -> Option<FractalKind> {
+    let mut kind = FractalKind::Mandelbrot;
+    for _ in 0 .. 3 {
+        if kind.name().trim_end_matches('\0') == name {
+            return Some(match kind {
+                FractalKind::Julia {..} => FractalKind::Julia {
+                    c: julia_c.map(|(re, im)| Complex { re, im }).unwrap_or(DEFAULT_JULIA_C),
+                },
+                _                       => kind,
+            });
+        }
+        kind = kind.next();
+    }
+    None
+}
+
+fn coloring_from_name(name: &str) -> Option<ColoringMode> {
+    let mut mode = ColoringMode::Palette;
+    loop {
+        if mode.name().trim_end_matches('\0') == name {
+            return Some(mode);
+        }
+        mode = mode.next();
+        if mode == ColoringMode::Palette {
+            return None;
+        }
+    }
+}
+
+pub fn load_view_state(path: &str) -> Result<ViewState, Box<dyn Error>> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+pub fn save_view_state(path: &str, state: &ViewState) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), state)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mandelbrot::INITIAL_VIEW;
+
+    #[test]
+    fn bookmark_keeps_fractal_and_rotation() {
+        let view     = View {
+            upper_left:  Complex { re: -1.0, im: 1.0 },
+            lower_right: Complex { re: 1.0,  im: -1.0 },
+        };
+        let julia    = FractalKind::Julia { c: Complex { re: 0.25, im: -0.5 } };
+        let json     = serde_json::to_string(&Bookmark::capture(view, 0.75, 500, julia)).unwrap();
+        let bookmark: Bookmark = serde_json::from_str(&json).unwrap();
+        assert_eq!(bookmark.rotation(), 0.75);
+        assert_eq!(bookmark.limit(), 500);
+        match bookmark.fractal().unwrap() {
+            FractalKind::Julia { c } => assert_eq!((c.re, c.im), (0.25, -0.5)),
+            _                        => panic!("bookmark lost the Julia set"),
+        }
+
+        let old: Bookmark = serde_json::from_str(
+            r#"{"upper_left": [-2.0, 1.0], "lower_right": [1.0, -1.0], "limit": 255}"#).unwrap();
+        assert_eq!(old.rotation(), 0.0);
+        match old.fractal().unwrap() {
+            FractalKind::Mandelbrot => {}
+            _                       => panic!("old bookmark should load on the Mandelbrot set"),
+        }
+    }
+
+    #[test]
+    fn view_state_keeps_a_deep_center() {
+        let offsets = View {
+            upper_left:  Complex { re: -1e-30, im: 1e-30 },
+            lower_right: Complex { re: 1e-30,  im: -1e-30 },
+        };
+        let deep    = PrecisePoint::parse("-1.749721929742338571743447069504", "4.27e-29").unwrap();
+        let state   = ViewState::capture(offsets, Some(&deep), 0.0, 1000, FractalKind::Mandelbrot, 0,
+                                         ColoringMode::Palette);
+        let json    = serde_json::to_string(&state).unwrap();
+        let loaded: ViewState = serde_json::from_str(&json).unwrap();
+        let (mut view, mut center, mut rotation, mut limit) = (INITIAL_VIEW, None, 0.0, 0);
+        let (mut fractal, mut palette, mut coloring)        = (FractalKind::Mandelbrot, 0, ColoringMode::Raw);
+        loaded.apply(&mut view, &mut center, &mut rotation, &mut limit, &mut fractal, &mut palette, &mut coloring,
+                     1).unwrap();
+        // serde_json may round the offsets by an ulp, but not the center.
+        assert!((view.lower_right.re - offsets.lower_right.re).abs() < 1e-44);
+        assert_eq!(center.unwrap().to_strings(), deep.to_strings());
+    }
+}
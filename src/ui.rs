@@ -0,0 +1,466 @@
+cpp!{{
+    #include <stdio.h>
+    #include <stdint.h>
+    #include "imgui/imgui.h"
+
+    // Mirrors the Rust `Stats` struct.
+    struct Stats {
+        const char *kernel;
+        const char *coloring;
+        const char *palette;
+        const char *fractal;
+        float       frame_ms;
+        float       fps;
+        float       render_ms;
+        double      zoom;
+        uint32_t    limit;
+        uint32_t    threads;
+        uint32_t    supersample;
+        int32_t     has_diff;
+        uint32_t    diff_max;
+        float       diff_mean;
+        int32_t     has_progress;
+        float       progress;
+        int32_t     has_julia;
+        double      julia_re;
+        double      julia_im;
+        int32_t     has_cursor;
+        double      cursor_re;
+        double      cursor_im;
+        uint32_t    errors;
+        uint32_t    glitches;
+    };
+}}
+
+use raster::{BlendMode, FontAtlas, RasterTarget, UiLayer, blend_over, draw_triangle};
+use render_thread::{DiffStats, FrameStats};
+use mandelbrot::{ColoringMode, Config, FractalKind, KernelWidth, View, INITIAL_VIEW};
+use num::Complex;
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::f32;
+use std::f64;
+use std::hash::{Hash, Hasher};
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+lazy_static! {
+    pub static ref GlobalUi: Mutex<UiLayer>   = Mutex::new(UiLayer { pixels: Vec::new(),
+                                                                     width:  0,
+                                                                     hash:   None,
+                                                                     blend:  BlendMode::Straight });
+    static ref GlobalFont:   Mutex<FontAtlas> = Mutex::new(FontAtlas { alpha: Vec::new(), width: 0, height: 0 });
+}
+
+pub fn invalidate_ui_cache() {
+    GlobalUi.lock().unwrap().hash = None;
+}
+
+pub fn composite_ui(pixels: &mut [u32]) {
+    let layer = GlobalUi.lock().unwrap();
+    for (pixel, &ui) in pixels.iter_mut().zip(layer.pixels.iter()) {
+        if ui != 0 {
+            *pixel = blend_over(ui, *pixel | 0xFF00_0000) & 0x00FF_FFFF;
+        }
+    }
+}
+
+// `key` identifies what the draw data shows; the layer is only rasterized
+// again when it changes.
+fn fetch_render_data(_im_draw_data: *const (), key: u64) {
+    let mut layer = GlobalUi.lock().unwrap();
+    if layer.hash == Some(key) {
+        return;
+    }
+    for pixel in layer.pixels.iter_mut() {
+        *pixel = 0;
+    }
+    layer.hash = Some(key);
+    let atlas  = GlobalFont.lock().unwrap();
+    rasterize_draw_data(_im_draw_data, &mut RasterTarget { layer: &mut layer, atlas: &atlas });
+}
+
+fn rasterize_draw_data(_im_draw_data: *const (), target: &mut RasterTarget) {
+    let rasterizer = draw_triangle as *const ();
+    let target     = target as *mut RasterTarget as *mut ();
+    unsafe {
+        cpp!([_im_draw_data as "void *", rasterizer as "void *", target as "void *"] {             
+            
+            struct Point2DF {
+                float X;
+                float Y;
+            };
+
+            typedef void DrawTriangle(void *target,
+                                      Point2DF* p0, Point2DF* p1, Point2DF* p2,
+                                      float R0, float G0, float B0, float A0,
+                                      float R1, float G1, float B1, float A1,
+                                      float R2, float G2, float B2, float A2,
+                                      Point2DF* uv0, Point2DF* uv1, Point2DF* uv2);      
+            DrawTriangle *rusterizer = (DrawTriangle *) rasterizer;
+            ImGuiIO& io = ImGui::GetIO();
+            int fb_width  = (int)(io.DisplaySize.x * io.DisplayFramebufferScale.x);
+            int fb_height = (int)(io.DisplaySize.y * io.DisplayFramebufferScale.y);
+            if (fb_width == 0 || fb_height == 0) {
+                printf("Skip frame\n");
+                return;
+            }
+            ImDrawData *data = (ImDrawData *)_im_draw_data;
+            ImVec2 fb_scale  = io.DisplayFramebufferScale;
+            data->ScaleClipRects(fb_scale);
+            for (int n = 0; n < data->CmdListsCount; n++) {
+                const ImDrawList *cmd_list = data->CmdLists[n];
+                unsigned int IndexOffset = 0;
+                for (int cmd_i = 0; cmd_i < cmd_list->CmdBuffer.Size; cmd_i++) {
+                    const ImDrawCmd *pcmd = &cmd_list->CmdBuffer[cmd_i];
+                    unsigned int ElementCount = (unsigned int)pcmd->ElemCount;
+                    const ImDrawIdx *indices  = cmd_list->IdxBuffer.Data + IndexOffset;
+                    if (pcmd->UserCallback) {
+                        printf("User input is not implemented.\n");
+                    } else {
+                        for (unsigned int i = 0; i < ElementCount; i+= 3) {
+                            unsigned int idx0 = (unsigned int)indices[i];
+                            unsigned int idx1 = (unsigned int)indices[i + 1];
+                            unsigned int idx2 = (unsigned int)indices[i + 2];
+
+                            Point2DF p0  = {cmd_list->VtxBuffer[idx0].pos.x * fb_scale.x,
+                                            cmd_list->VtxBuffer[idx0].pos.y * fb_scale.y};
+                            Point2DF p1  = {cmd_list->VtxBuffer[idx1].pos.x * fb_scale.x,
+                                            cmd_list->VtxBuffer[idx1].pos.y * fb_scale.y};
+                            Point2DF p2  = {cmd_list->VtxBuffer[idx2].pos.x * fb_scale.x,
+                                            cmd_list->VtxBuffer[idx2].pos.y * fb_scale.y};
+
+                            Point2DF uv0 = {cmd_list->VtxBuffer[idx0].uv.x,
+                                            cmd_list->VtxBuffer[idx0].uv.y};
+                            Point2DF uv1 = {cmd_list->VtxBuffer[idx1].uv.x,
+                                            cmd_list->VtxBuffer[idx1].uv.y};
+                            Point2DF uv2 = {cmd_list->VtxBuffer[idx2].uv.x,
+                                            cmd_list->VtxBuffer[idx2].uv.y};
+                            
+                            ImVec4 rgba0 = ImGui::ColorConvertU32ToFloat4(cmd_list->VtxBuffer[idx0].col);
+                            ImVec4 rgba1 = ImGui::ColorConvertU32ToFloat4(cmd_list->VtxBuffer[idx1].col);
+                            ImVec4 rgba2 = ImGui::ColorConvertU32ToFloat4(cmd_list->VtxBuffer[idx2].col);
+                            
+                            rusterizer(target, &p0, &p1, &p2,
+                                       rgba0.x, rgba0.y, rgba0.z, rgba0.w,
+                                       rgba1.x, rgba1.y, rgba1.z, rgba1.w,
+                                       rgba2.x, rgba2.y, rgba2.z, rgba2.w,
+                                       &uv0, &uv1, &uv2);
+                        }
+                    }
+                    IndexOffset += ElementCount;
+                }
+            }
+        });
+    }    
+}
+
+// imgui lays out in logical units of DisplaySize; the rasterizer scales
+// vertices back up by `ui_scale` into framebuffer pixels.
+pub fn init_imgui(config: &Config, ui_scale: f32) {
+    unsafe {
+        let w = config.width  as u32;
+        let h = config.height as u32;
+        cpp!([w as "int32_t", h as "int32_t", ui_scale as "float"] {
+            printf("Starting imgui initialization...\n");
+            ImGui::CreateContext();
+            ImGuiIO& io = ImGui::GetIO();
+            io.DisplayFramebufferScale = ImVec2(ui_scale, ui_scale);
+            io.DisplaySize = ImVec2((float)w / ui_scale, (float)h / ui_scale);
+            unsigned char *font_texture = NULL;
+            int tex_w, tex_h, tex_bpp;
+            io.Fonts->GetTexDataAsAlpha8(&font_texture, &tex_w, &tex_h, &tex_bpp);
+            printf("OK: Finishing imgui initialization.\n");
+        });
+    }
+    load_font_atlas();
+}
+
+// imgui owns the texture memory, so the atlas is copied out once after the
+// fonts are built.
+fn load_font_atlas() {
+    let mut pixels: *const u8 = ptr::null();
+    let mut width:  i32       = 0;
+    let mut height: i32       = 0;
+    let pixels_ptr = &mut pixels as *mut *const u8;
+    let width_ptr  = &mut width  as *mut i32;
+    let height_ptr = &mut height as *mut i32;
+    unsafe {
+        cpp!([pixels_ptr as "unsigned char **", width_ptr as "int32_t *", height_ptr as "int32_t *"] {
+            ImGui::GetIO().Fonts->GetTexDataAsAlpha8(pixels_ptr, width_ptr, height_ptr);
+        });
+    }
+    if pixels.is_null() || width <= 0 || height <= 0 {
+        return;
+    }
+    let (width, height) = (width as usize, height as usize);
+    let alpha = unsafe { slice::from_raw_parts(pixels, width * height) }.to_vec();
+    *GlobalFont.lock().unwrap() = FontAtlas { alpha, width, height };
+}
+
+pub fn font_atlas_size() -> (i32, i32) {
+    let mut width:  i32 = 0;
+    let mut height: i32 = 0;
+    let width_ptr  = &mut width  as *mut i32;
+    let height_ptr = &mut height as *mut i32;
+    unsafe {
+        cpp!([width_ptr as "int32_t *", height_ptr as "int32_t *"] {
+            unsigned char *font_texture = NULL;
+            ImGui::GetIO().Fonts->GetTexDataAsAlpha8(&font_texture, width_ptr, height_ptr);
+        });
+    }
+    (width, height)
+}
+
+// Width of ImDrawIdx in the imgui build, 2 by default or 4 with the 32-bit
+// index config. The rasterizer and draw_data_hash read indices through
+// ImDrawIdx and sizeof, so either works; anything else is a broken build.
+pub fn draw_index_size() -> usize {
+    unsafe {
+        cpp!([] -> usize as "size_t" {
+            return sizeof(ImDrawIdx);
+        })
+    }
+}
+
+pub fn shutdown_imgui() {
+    unsafe {
+        cpp!([] {
+            ImGui::DestroyContext();
+        });
+    }
+}
+
+// Substituted for a zero or negative frame delta (first frame, coarse clock),
+// which imgui does not accept.
+const DEFAULT_DELTA_TIME: f32 = 1.0 / 60.0;
+
+// Display frames averaged into the overlay's frame time, about a second's
+// worth; one slow frame after a re-render then barely moves the number.
+const FRAME_TIME_WINDOW: usize = 60;
+
+// Display frames between updates of the frame time the overlay shows. In
+// between the Stats text stays the same and its cached layer is reused.
+const FRAME_TIME_REFRESH: usize = 30;
+
+pub struct FrameTimer {
+    samples: VecDeque<f32>,
+    shown:   f32,
+    since:   usize,
+}
+
+impl FrameTimer {
+    pub fn new() -> FrameTimer {
+        FrameTimer { samples: VecDeque::with_capacity(FRAME_TIME_WINDOW), shown: 0.0, since: FRAME_TIME_REFRESH }
+    }
+
+    pub fn record(&mut self, ms: f32) {
+        if self.samples.len() == FRAME_TIME_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms);
+        self.since += 1;
+        if self.since >= FRAME_TIME_REFRESH {
+            self.shown = self.average_ms();
+            self.since = 0;
+        }
+    }
+
+    pub fn average_ms(&self) -> f32 {
+        if self.samples.is_empty() { 0.0 } else { self.samples.iter().sum::<f32>() / self.samples.len() as f32 }
+    }
+
+    // The average as of the last refresh, for the overlay.
+    pub fn shown_ms(&self) -> f32 {
+        self.shown
+    }
+}
+
+// Everything the Stats overlay shows, filled in once per display frame and
+// read field by field by render_stats. The layout must match `struct Stats`
+// in the cpp! prelude.
+#[repr(C)]
+pub struct Stats {
+    kernel:       *const u8,
+    coloring:     *const u8,
+    palette:      *const u8,
+    fractal:      *const u8,
+    frame_ms:     f32,
+    fps:          f32,
+    render_ms:    f32,
+    zoom:         f64,
+    limit:        u32,
+    threads:      u32,
+    supersample:  u32,
+    has_diff:     i32,
+    diff_max:     u32,
+    diff_mean:    f32,
+    has_progress: i32,
+    progress:     f32,
+    has_julia:    i32,
+    julia_re:     f64,
+    julia_im:     f64,
+    has_cursor:   i32,
+    cursor_re:    f64,
+    cursor_im:    f64,
+    errors:       u32,
+    glitches:     u32,
+}
+
+impl Stats {
+    pub fn new(kernel:     KernelWidth,
+               coloring:   ColoringMode,
+               palette:    &str,
+               fractal:    FractalKind,
+               view:       View,
+               cursor:     Option<Complex<f64>>,
+               frame:      FrameStats,
+               config:     &Config,
+               frame_ms:   f32) -> Stats {
+        let diff    = frame.diff.unwrap_or(DiffStats { max: 0, mean: 0.0 });
+        let julia_c = match fractal {
+            FractalKind::Julia { c } => c,
+            _                        => Complex { re: 0.0, im: 0.0 },
+        };
+        Stats {
+            kernel:       kernel.name().as_ptr(),
+            coloring:     coloring.name().as_ptr(),
+            palette:      palette.as_ptr(),
+            fractal:      fractal.name().as_ptr(),
+            frame_ms,
+            fps:          if frame_ms > 0.0 { 1000.0 / frame_ms } else { 0.0 },
+            render_ms:    frame.render_ms,
+            zoom:         (INITIAL_VIEW.lower_right.re - INITIAL_VIEW.upper_left.re)
+                              / (view.lower_right.re - view.upper_left.re),
+            limit:        if frame.limit > 0 { frame.limit } else { config.limit },
+            threads:      config.num_threads as u32,
+            supersample:  config.supersample as u32,
+            has_diff:     frame.diff.is_some() as i32,
+            diff_max:     diff.max,
+            diff_mean:    diff.mean,
+            has_progress: frame.progress.is_some() as i32,
+            progress:     frame.progress.unwrap_or(1.0) * 100.0,
+            has_julia:    fractal.julia_c().is_some() as i32,
+            julia_re:     julia_c.re,
+            julia_im:     julia_c.im,
+            has_cursor:   cursor.is_some() as i32,
+            cursor_re:    cursor.map(|c| c.re).unwrap_or(0.0),
+            cursor_im:    cursor.map(|c| c.im).unwrap_or(0.0),
+            errors:       frame.errors as u32,
+            glitches:     frame.glitches,
+        }
+    }
+
+    // Everything the overlay prints, plus the window size; equal keys give
+    // the same layer. The strings live as long as the window, so their address
+    // will do.
+    fn layer_key(&self, w: u32, h: u32) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.kernel as usize, self.coloring as usize, self.palette as usize, self.fractal as usize).hash(&mut hasher);
+        (self.frame_ms.to_bits(), self.fps.to_bits(), self.render_ms.to_bits(), self.zoom.to_bits()).hash(&mut hasher);
+        (self.limit, self.threads, self.supersample, self.errors, self.glitches).hash(&mut hasher);
+        (self.has_diff, self.diff_max, self.diff_mean.to_bits()).hash(&mut hasher);
+        (self.has_progress, self.progress.to_bits()).hash(&mut hasher);
+        (self.has_julia, self.julia_re.to_bits(), self.julia_im.to_bits()).hash(&mut hasher);
+        (self.has_cursor, self.cursor_re.to_bits(), self.cursor_im.to_bits()).hash(&mut hasher);
+        (w, h).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+pub fn render_stats(stats: &Stats, config: &Config, delta_time: f32) {
+    let w          = config.width  as u32;
+    let h          = config.height as u32;
+    let key        = stats.layer_key(w, h);
+    let delta_time = if delta_time > 0.0 { delta_time } else { DEFAULT_DELTA_TIME };
+    let stats      = stats as *const Stats;
+    // imgui no longer calls back with the draw data, so it is fetched once the
+    // frame is rendered.
+    let draw_data  = unsafe {
+        cpp!([w as "int32_t", h as "int32_t", delta_time as "float", stats as "const Stats *"]
+             -> *const () as "const void *" {
+            ImGuiIO& io = ImGui::GetIO();
+            io.DisplaySize = ImVec2(w / io.DisplayFramebufferScale.x, h / io.DisplayFramebufferScale.y);
+            io.DeltaTime   = delta_time;
+            ImGui::NewFrame();
+            ImGui::Begin("Stats", 0);
+            ImGui::SetWindowPos("Stats", ImVec2(10, 10));
+            int extra_lines = stats->has_diff + stats->has_progress + stats->has_julia + stats->has_cursor
+                            + (stats->errors > 0) + (stats->glitches > 0);
+            ImGui::SetWindowSize(ImVec2(300, 165 + 20 * extra_lines));
+            
+            ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(1.0f, 0.2f, 0.2f, 1.0f));
+            ImGui::Text("Milliseconds per frame: %.2f (%.1f FPS)", stats->frame_ms, stats->fps);
+            ImGui::PopStyleColor();
+            ImGui::Text("Kernel: %-6s %-7s  %.2f ms", stats->kernel, stats->coloring, stats->render_ms);
+            ImGui::Text("Fractal: %s", stats->fractal);
+            ImGui::Text("Palette: %s", stats->palette);
+            ImGui::Text("Threads: %u  SSAA: %ux", stats->threads, stats->supersample);
+            ImGui::Text("Zoom: x%.3g  Limit: %u", stats->zoom, stats->limit);
+            if (stats->has_diff) {
+                ImGui::Text("Diff: max %u  mean %.3f", stats->diff_max, stats->diff_mean);
+            }
+            if (stats->has_progress) {
+                ImGui::Text("Refinement: %.0f%%", stats->progress);
+            }
+            if (stats->has_julia) {
+                ImGui::Text("Julia c: %.6f %+.6fi", stats->julia_re, stats->julia_im);
+            }
+            if (stats->has_cursor) {
+                ImGui::Text("Re: %.12f  Im: %.12f", stats->cursor_re, stats->cursor_im);
+            }
+            if (stats->errors > 0) {
+                ImGui::Text("Error pixels: %u", stats->errors);
+            }
+            if (stats->glitches > 0) {
+                ImGui::Text("Glitch pixels: %u", stats->glitches);
+            }
+
+            ImGui::End();
+            ImGui::Render();
+            ImGui::EndFrame();
+            return ImGui::GetDrawData();
+        })
+    };
+    fetch_render_data(draw_data, key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // imgui keeps one global context, so tests that touch it take turns.
+    static IMGUI: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn imgui_builds_font_atlas() {
+        let _imgui = IMGUI.lock().unwrap();
+        init_imgui(&Config { width: 320, height: 240, ..Config::default() }, 1.0);
+        let (width, height) = font_atlas_size();
+        assert!(width > 0 && height > 0, "imgui font atlas is empty, check the imgui build");
+        assert_eq!(GlobalFont.lock().unwrap().alpha.len(), (width * height) as usize);
+        let index_size = draw_index_size();
+        assert!(index_size == 2 || index_size == 4, "unexpected ImDrawIdx size {}", index_size);
+        shutdown_imgui();
+    }
+
+    #[test]
+    fn shown_frame_time_holds_between_refreshes() {
+        let mut timer = FrameTimer::new();
+        timer.record(10.0);
+        assert_eq!(timer.shown_ms(), 10.0);
+        let stats = |ms| Stats::new(KernelWidth::Scalar, ColoringMode::Palette, "default", FractalKind::Mandelbrot,
+                                    INITIAL_VIEW, None, FrameStats::default(), &Config::default(), ms);
+        let key   = stats(timer.shown_ms()).layer_key(320, 240);
+        for _ in 1..FRAME_TIME_REFRESH {
+            timer.record(30.0);
+            assert_eq!(timer.shown_ms(), 10.0);
+            assert_eq!(stats(timer.shown_ms()).layer_key(320, 240), key);
+        }
+        timer.record(30.0);
+        assert!(timer.shown_ms() > 10.0);
+        assert!(stats(timer.shown_ms()).layer_key(320, 240) != key);
+        assert!(stats(10.0).layer_key(640, 480) != key);
+    }
+}